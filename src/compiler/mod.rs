@@ -1,43 +1,119 @@
+use crate::compiler::output::Output;
 use crate::compiler::symbol_table::{
     Identifier, SymbolTable, Variable, VariableKind, VariableScope, VariableType,
 };
 use crate::tokenizer::*;
-use anyhow::{bail, Result};
-use std::fs::File;
+use crate::{
+    CallInfo, Extension, MissingExtension, Options, Precedence, SubroutineInfo, SubroutineKind,
+};
+use anyhow::{anyhow, bail, Result};
 use std::io::Write;
 use std::iter::Peekable;
 
+mod output;
 mod symbol_table;
 
 pub struct Compiler<'a> {
     tokenizer: &'a mut Peekable<Tokenizer<'a>>,
-    output: &'a mut File,
+    output: Output<'a>,
+    options: &'a Options,
     current_token: Option<Token>,
+    current_class_name: Option<String>,
+    current_subroutine_name: Option<String>,
+    loop_depth: usize,
+    // Number of enclosing loops and switch statements, which `break` leaves.
+    break_depth: usize,
     current_identifier_name: Option<String>,
     current_identifier_category: Option<String>,
     current_identifier_index: usize,
     current_identifier_usage: Option<String>,
+    // Type given by the last compile_type, for the variables declared after it.
+    current_variable_type: Option<VariableType>,
     class_symbol_table: SymbolTable,
     subroutine_symbol_table: SymbolTable,
+    // Number of local slots used by the current subroutine (its nLocals).
+    subroutine_local_count: usize,
+    // Start, in the subroutine symbol table, of the innermost block's declarations.
+    block_start: usize,
+    // Subroutines of the class compiled so far. The last one is the current subroutine.
+    subroutines: Vec<SubroutineInfo>,
+    // Classes named as a type or as the receiver of a call such as `Output.printInt(...)`.
+    referenced_classes: Vec<String>,
+    // Static variables with an initializer, in the order they are declared.
+    static_initializers: Vec<String>,
+    // Calls made by the static initializers, as in SubroutineInfo::calls.
+    static_initializer_calls: Vec<CallInfo>,
+    // Warnings found so far, such as those of --lint-precedence.
+    warnings: Vec<String>,
 }
 
 impl<'a> Compiler<'a> {
-    pub fn new(tokenizer: &'a mut Peekable<Tokenizer<'a>>, output: &'a mut File) -> Self {
+    pub fn new(
+        tokenizer: &'a mut Peekable<Tokenizer<'a>>,
+        output: &'a mut dyn Write,
+        options: &'a Options,
+    ) -> Self {
         Compiler {
             tokenizer,
-            output,
+            output: Output::new(output),
+            options,
             current_token: None,
+            current_class_name: None,
+            current_subroutine_name: None,
+            loop_depth: 0,
+            break_depth: 0,
             current_identifier_name: None,
             current_identifier_category: None,
             current_identifier_index: 0,
             current_identifier_usage: None,
+            current_variable_type: None,
             class_symbol_table: Vec::new(),
             subroutine_symbol_table: Vec::new(),
+            subroutine_local_count: 0,
+            block_start: 0,
+            subroutines: Vec::new(),
+            referenced_classes: Vec::new(),
+            static_initializers: Vec::new(),
+            static_initializer_calls: Vec::new(),
+            warnings: Vec::new(),
+        }
+    }
+
+    pub fn class_name(&self) -> &str {
+        self.current_class_name.as_deref().unwrap_or("")
+    }
+
+    // Other classes the class uses, by name, in the order they first appear.
+    pub fn referenced_classes(&self) -> Vec<String> {
+        let mut classes: Vec<String> = Vec::new();
+
+        for class in &self.referenced_classes {
+            if class != self.class_name() && !classes.contains(class) {
+                classes.push(class.clone());
+            }
         }
+
+        classes
+    }
+
+    pub fn static_initializers(&self) -> &[String] {
+        &self.static_initializers
+    }
+
+    pub fn static_initializer_calls(&self) -> &[CallInfo] {
+        &self.static_initializer_calls
+    }
+
+    pub fn subroutines(&self) -> &[SubroutineInfo] {
+        &self.subroutines
+    }
+
+    pub fn warnings(&self) -> &[String] {
+        &self.warnings
     }
 
     pub fn compile(&mut self) -> Result<()> {
-        self.current_token = self.tokenizer.next();
+        self.advance()?;
 
         match &self.current_token {
             Some(Token::Keyword(Keyword::Class)) => self.compile_class(),
@@ -92,9 +168,17 @@ impl<'a> Compiler<'a> {
             Keyword::Else => write!(self.output, "<keyword> else </keyword>\n")?,
             Keyword::While => write!(self.output, "<keyword> while </keyword>\n")?,
             Keyword::Return => write!(self.output, "<keyword> return </keyword>\n")?,
+            Keyword::For => write!(self.output, "<keyword> for </keyword>\n")?,
+            Keyword::Break => write!(self.output, "<keyword> break </keyword>\n")?,
+            Keyword::Continue => write!(self.output, "<keyword> continue </keyword>\n")?,
+            Keyword::Switch => write!(self.output, "<keyword> switch </keyword>\n")?,
+            Keyword::Case => write!(self.output, "<keyword> case </keyword>\n")?,
+            Keyword::Default => write!(self.output, "<keyword> default </keyword>\n")?,
+            Keyword::Const => write!(self.output, "<keyword> const </keyword>\n")?,
+            Keyword::Enum => write!(self.output, "<keyword> enum </keyword>\n")?,
         }
 
-        self.current_token = self.tokenizer.next();
+        self.advance()?;
         Ok(())
     }
 
@@ -143,9 +227,16 @@ impl<'a> Compiler<'a> {
             Symbol::GreaterThan => write!(self.output, "<symbol> &gt; </symbol>\n")?,
             Symbol::Equal => write!(self.output, "<symbol> = </symbol>\n")?,
             Symbol::Not => write!(self.output, "<symbol> ~ </symbol>\n")?,
+            Symbol::Colon => write!(self.output, "<symbol> : </symbol>\n")?,
+            Symbol::PlusEqual => write!(self.output, "<symbol> += </symbol>\n")?,
+            Symbol::MinusEqual => write!(self.output, "<symbol> -= </symbol>\n")?,
+            Symbol::AndEqual => write!(self.output, "<symbol> &amp;= </symbol>\n")?,
+            Symbol::OrEqual => write!(self.output, "<symbol> |= </symbol>\n")?,
+            Symbol::Increment => write!(self.output, "<symbol> ++ </symbol>\n")?,
+            Symbol::Decrement => write!(self.output, "<symbol> -- </symbol>\n")?,
         }
 
-        self.current_token = self.tokenizer.next();
+        self.advance()?;
         Ok(())
     }
 
@@ -164,13 +255,26 @@ impl<'a> Compiler<'a> {
             ),
         }
 
-        self.current_token = self.tokenizer.next();
+        self.advance()?;
         Ok(())
     }
 
     fn compile_string_constant(&mut self) -> Result<()> {
         match &self.current_token {
             Some(Token::StringConstant(value)) => {
+                // Escape sequences can produce Hack codes such as newline (128), which are
+                // control characters in Unicode and are written as character references.
+                let value: String = value
+                    .chars()
+                    .map(|character| {
+                        if character.is_control() {
+                            format!("&#{};", character as u32)
+                        } else {
+                            character.to_string()
+                        }
+                    })
+                    .collect();
+
                 write!(
                     self.output,
                     "<stringConstant> {} </stringConstant>\n",
@@ -183,7 +287,7 @@ impl<'a> Compiler<'a> {
             ),
         }
 
-        self.current_token = self.tokenizer.next();
+        self.advance()?;
         Ok(())
     }
 
@@ -202,6 +306,8 @@ impl<'a> Compiler<'a> {
                 self.current_identifier_name = Some(value.to_string());
 
                 if self.current_identifier_category == Some("static".to_string()) {
+                    self.check_not_constant(value)?;
+
                     self.current_identifier_index = self
                         .class_symbol_table
                         .iter()
@@ -214,13 +320,18 @@ impl<'a> Compiler<'a> {
                     let value = Variable {
                         variable_name: self.current_identifier_name.clone().unwrap(),
                         variable_kind: VariableKind::Static,
-                        variable_type: VariableType::Integer,
+                        variable_type: self
+                            .current_variable_type
+                            .clone()
+                            .unwrap_or(VariableType::Integer),
                         variable_scope: VariableScope::ClassLevel,
                         variable_index: self.current_identifier_index,
                     };
 
                     self.class_symbol_table.push(value);
                 } else if self.current_identifier_category == Some("field".to_string()) {
+                    self.check_not_constant(value)?;
+
                     self.current_identifier_index = self
                         .class_symbol_table
                         .iter()
@@ -233,60 +344,81 @@ impl<'a> Compiler<'a> {
                     let value = Variable {
                         variable_name: self.current_identifier_name.clone().unwrap(),
                         variable_kind: VariableKind::Field,
-                        variable_type: VariableType::Integer,
+                        variable_type: self
+                            .current_variable_type
+                            .clone()
+                            .unwrap_or(VariableType::Integer),
                         variable_scope: VariableScope::ClassLevel,
                         variable_index: self.current_identifier_index,
                     };
 
                     self.class_symbol_table.push(value);
                 } else if self.current_identifier_category == Some("local".to_string()) {
-                    self.current_identifier_index = self
-                        .class_symbol_table
+                    if self.subroutine_symbol_table[self.block_start..]
                         .iter()
-                        .filter(|element| match element.variable_kind {
-                            VariableKind::Variable => true,
-                            _ => false,
-                        })
-                        .count();
+                        .any(|element| &element.variable_name == value)
+                    {
+                        bail!(
+                            "{} is already declared in {}.{}.",
+                            value,
+                            self.current_class_name.as_deref().unwrap_or(""),
+                            self.current_subroutine_name.as_deref().unwrap_or("")
+                        );
+                    }
+
+                    // Every declaration gets a fresh slot, even when an earlier block's slot is no
+                    // longer in use.
+                    self.current_identifier_index = self.subroutine_local_count;
+                    self.subroutine_local_count += 1;
 
                     let value = Variable {
                         variable_name: self.current_identifier_name.clone().unwrap(),
                         variable_kind: VariableKind::Variable,
-                        variable_type: VariableType::Integer,
+                        variable_type: self
+                            .current_variable_type
+                            .clone()
+                            .unwrap_or(VariableType::Integer),
                         variable_scope: VariableScope::SubroutineLevel,
                         variable_index: self.current_identifier_index,
                     };
 
-                    self.class_symbol_table.push(value);
+                    self.subroutine_symbol_table.push(value);
                 } else if self.current_identifier_category == Some("argument".to_string()) {
-                    self.current_identifier_index = self
-                        .class_symbol_table
-                        .iter()
-                        .filter(|element| match element.variable_kind {
-                            VariableKind::Argument => true,
-                            _ => false,
-                        })
-                        .count();
+                    // Argument 0 of a method is the object it is called on.
+                    let first_index = match self.current_subroutine_kind() {
+                        Some(SubroutineKind::Method) => 1,
+                        _ => 0,
+                    };
+
+                    self.current_identifier_index = first_index
+                        + self
+                            .subroutine_symbol_table
+                            .iter()
+                            .filter(|element| match element.variable_kind {
+                                VariableKind::Argument => true,
+                                _ => false,
+                            })
+                            .count();
 
                     let value = Variable {
                         variable_name: self.current_identifier_name.clone().unwrap(),
                         variable_kind: VariableKind::Argument,
-                        variable_type: VariableType::Integer,
+                        variable_type: self
+                            .current_variable_type
+                            .clone()
+                            .unwrap_or(VariableType::Integer),
                         variable_scope: VariableScope::SubroutineLevel,
                         variable_index: self.current_identifier_index,
                     };
 
-                    self.class_symbol_table.push(value);
+                    self.subroutine_symbol_table.push(value);
                 }
 
-                write!(
-                        self.output,
-                        "<identifier> \n<name> {} </name> \n<category> {} </category> \n<index> {} </index> \n<usage> {} </usage> \n</identifier>\n",
-                        self.current_identifier_name.as_ref().unwrap_or(&"".to_string()),
-                        self.current_identifier_category.as_ref().unwrap_or(&"".to_string()),
-                        self.current_identifier_index,
-                        self.current_identifier_usage.as_ref().unwrap_or(&"".to_string()),
-                    )?;
+                let name = self.current_identifier_name.clone().unwrap_or_default();
+                let category = self.current_identifier_category.clone().unwrap_or_default();
+                let usage = self.current_identifier_usage.clone().unwrap_or_default();
+
+                self.write_identifier(&name, &category, self.current_identifier_index, &usage)?;
 
                 self.current_identifier_index = 0;
             }
@@ -296,10 +428,87 @@ impl<'a> Compiler<'a> {
             ),
         }
 
-        self.current_token = self.tokenizer.next();
+        self.advance()?;
+        Ok(())
+    }
+
+    fn write_identifier(
+        &mut self,
+        name: &str,
+        category: &str,
+        index: usize,
+        usage: &str,
+    ) -> Result<()> {
+        write!(
+            self.output,
+            "<identifier> \n<name> {} </name> \n<category> {} </category> \n<index> {} </index> \n<usage> {} </usage> \n</identifier>\n",
+            name, category, index, usage,
+        )?;
+
         Ok(())
     }
 
+    // A variable used in a statement or expression, annotated with its kind and index. The
+    // variable must be declared in the subroutine, in an enclosing block, or in the class.
+    fn compile_variable(&mut self) -> Result<()> {
+        let name = self.current_identifier()?;
+
+        let (category, index) = match self.find_variable(&name) {
+            Some(variable) => (
+                variable.variable_kind.name().to_string(),
+                variable.variable_index,
+            ),
+            None => bail!("{} is not declared in {}.", name, self.location()),
+        };
+
+        self.write_identifier(&name, &category, index, "use")?;
+        self.current_identifier_name = Some(name);
+
+        self.advance()
+    }
+
+    // A class or subroutine name used in a statement or expression.
+    fn compile_reference(&mut self, category: &str) -> Result<()> {
+        let name = self.current_identifier()?;
+
+        self.write_identifier(&name, category, 0, "use")?;
+        self.current_identifier_name = Some(name);
+
+        self.advance()
+    }
+
+    fn current_identifier(&self) -> Result<String> {
+        match &self.current_token {
+            Some(Token::Identifier(IdentifierValue { value })) => Ok(value.clone()),
+            _ => bail!(
+                "Expected to find identifier. But found this instead: {:?}.",
+                self.current_token
+            ),
+        }
+    }
+
+    fn advance(&mut self) -> Result<()> {
+        self.current_token = match self.tokenizer.next().transpose() {
+            Ok(token) => token,
+            // The tokenizer doesn't know the location, so it is added here.
+            Err(error) => match error.downcast::<MissingExtension>() {
+                Ok(missing) => {
+                    return self.require_extension(missing.extension, &missing.usage);
+                }
+                Err(error) => return Err(self.located(error)),
+            },
+        };
+
+        Ok(())
+    }
+
+    fn peek_token(&mut self) -> Option<&Token> {
+        match self.tokenizer.peek() {
+            Some(Ok(token)) => Some(token),
+            _ => None,
+        }
+    }
+
     // Pogram structure
     fn compile_class(&mut self) -> Result<()> {
         self.current_identifier_category = Some("class".to_string());
@@ -309,14 +518,23 @@ impl<'a> Compiler<'a> {
 
         self.compile_keyword(Keyword::Class)?;
         self.compile_identifier()?;
+        self.current_class_name = self.current_identifier_name.clone();
         self.compile_symbol(Symbol::LeftCurlyBracket)?;
 
         while self.current_token == Some(Token::Keyword(Keyword::Static))
             || self.current_token == Some(Token::Keyword(Keyword::Field))
+            || self.current_token == Some(Token::Keyword(Keyword::Const))
+            || self.current_token == Some(Token::Keyword(Keyword::Enum))
         {
-            self.compile_classvardec()?;
+            match self.current_token {
+                Some(Token::Keyword(Keyword::Const)) => self.compile_constdec()?,
+                Some(Token::Keyword(Keyword::Enum)) => self.compile_enumdec()?,
+                _ => self.compile_classvardec()?,
+            }
         }
 
+        self.check_extension_keyword()?;
+
         while self.current_token == Some(Token::Keyword(Keyword::Constructor))
             || self.current_token == Some(Token::Keyword(Keyword::Function))
             || self.current_token == Some(Token::Keyword(Keyword::Method))
@@ -361,9 +579,17 @@ impl<'a> Compiler<'a> {
 
         // TODO: Create symbol talbe entry here. and clear current variable info.
 
+        if self.current_token == Some(Token::Symbol(Symbol::Equal)) {
+            self.compile_static_initializer()?;
+        }
+
         while self.current_token == Some(Token::Symbol(Symbol::Comma)) {
             self.compile_symbol(Symbol::Comma)?;
             self.compile_identifier()?;
+
+            if self.current_token == Some(Token::Symbol(Symbol::Equal)) {
+                self.compile_static_initializer()?;
+            }
         }
 
         self.compile_symbol(Symbol::Semicolon)?;
@@ -375,20 +601,236 @@ impl<'a> Compiler<'a> {
         Ok(())
     }
 
+    // '=' expression after the name of a static variable. The initializer stays here in the parse
+    // tree; the classes it uses decide when it can run (see compile_program).
+    fn compile_static_initializer(&mut self) -> Result<()> {
+        let name = self.current_identifier_name.clone().unwrap_or_default();
+
+        self.require_extension(
+            Extension::StaticInitializers,
+            &format!("initializer of static variable {}", name),
+        )?;
+
+        if self.current_identifier_category != Some("static".to_string()) {
+            bail!(
+                "Only static variables can have an initializer, but {}.{} is a field.",
+                self.class_name(),
+                name
+            );
+        }
+
+        self.compile_symbol(Symbol::Equal)?;
+        self.compile_expression()?;
+
+        self.static_initializers.push(name);
+
+        Ok(())
+    }
+
+    // const type NAME '=' constantExpression (',' NAME '=' constantExpression)* ';'
+    fn compile_constdec(&mut self) -> Result<()> {
+        write!(self.output, "<constDec>\n")?;
+
+        self.current_identifier_category = Some("class".to_string());
+        self.current_identifier_usage = Some("declaration".to_string());
+
+        self.compile_keyword(Keyword::Const)?;
+
+        // Checked before compile_type, which would record a class name as a class the program
+        // uses.
+        if let Some(Token::Identifier(IdentifierValue { value })) = &self.current_token {
+            bail!(
+                "Constants must be of type int, char or boolean, but class {} declares constants of type {}.",
+                self.class_name(),
+                value
+            );
+        }
+
+        self.compile_type()?;
+
+        let constant_type = self
+            .current_variable_type
+            .clone()
+            .unwrap_or(VariableType::Integer);
+
+        self.current_identifier_category = Some("constant".to_string());
+
+        self.compile_constant_definition(&constant_type)?;
+
+        while self.current_token == Some(Token::Symbol(Symbol::Comma)) {
+            self.compile_symbol(Symbol::Comma)?;
+            self.compile_constant_definition(&constant_type)?;
+        }
+
+        self.compile_symbol(Symbol::Semicolon)?;
+
+        self.current_identifier_category = None;
+        self.current_identifier_usage = None;
+
+        write!(self.output, "</constDec>\n")?;
+        Ok(())
+    }
+
+    // NAME '=' constantExpression. The expression is evaluated right away and written out as its
+    // value. A boolean constant must be true (-1) or false (0).
+    fn compile_constant_definition(&mut self, constant_type: &VariableType) -> Result<()> {
+        self.compile_identifier()?;
+
+        let name = self.current_identifier_name.clone().unwrap_or_default();
+
+        self.compile_symbol(Symbol::Equal)?;
+
+        let value = self.evaluate_constant_expression()?;
+
+        if *constant_type == VariableType::Boolean && value != -1 && value != 0 {
+            bail!(
+                "Boolean constant {} in class {} must be true or false, but its value is {}.",
+                name,
+                self.class_name(),
+                value
+            );
+        }
+
+        write!(self.output, "<expression>\n<term>\n")?;
+        self.write_constant(value)?;
+        write!(self.output, "</term>\n</expression>\n")?;
+
+        self.define_constant(name, value, constant_type.clone())
+    }
+
+    // enum Name '{' (NAME ('=' constantExpression)? ','?)* '}'. Members are numbered from 0, or
+    // from one more than the previous member.
+    fn compile_enumdec(&mut self) -> Result<()> {
+        write!(self.output, "<enumDec>\n")?;
+
+        self.current_identifier_category = Some("enum".to_string());
+        self.current_identifier_usage = Some("declaration".to_string());
+
+        self.compile_keyword(Keyword::Enum)?;
+        self.compile_identifier()?;
+        self.compile_symbol(Symbol::LeftCurlyBracket)?;
+
+        self.current_identifier_category = Some("constant".to_string());
+
+        let mut next_value = Some(0);
+
+        while let Some(Token::Identifier(_)) = self.current_token {
+            self.compile_identifier()?;
+
+            let name = self.current_identifier_name.clone().unwrap_or_default();
+
+            let value = if self.current_token == Some(Token::Symbol(Symbol::Equal)) {
+                self.compile_symbol(Symbol::Equal)?;
+
+                let value = self.evaluate_constant_expression()?;
+
+                write!(self.output, "<expression>\n<term>\n")?;
+                self.write_constant(value)?;
+                write!(self.output, "</term>\n</expression>\n")?;
+
+                value
+            } else {
+                match next_value {
+                    Some(value) => value,
+                    None => bail!("The value of enum member {} is out of range.", name),
+                }
+            };
+
+            self.define_constant(name, value, VariableType::Integer)?;
+
+            next_value = value.checked_add(1);
+
+            if self.current_token != Some(Token::Symbol(Symbol::Comma)) {
+                break;
+            }
+
+            self.compile_symbol(Symbol::Comma)?;
+        }
+
+        self.compile_symbol(Symbol::RightCurlyBracket)?;
+
+        self.current_identifier_category = None;
+        self.current_identifier_usage = None;
+
+        write!(self.output, "</enumDec>\n")?;
+        Ok(())
+    }
+
+    // Fails if `name` is already declared as a constant of the class. Constants share the
+    // namespace of static and field variables.
+    fn check_not_constant(&self, name: &str) -> Result<()> {
+        if self.class_symbol_table.iter().any(|element| {
+            element.variable_name == name
+                && matches!(element.variable_kind, VariableKind::Constant(_))
+        }) {
+            bail!(
+                "{} is already declared in class {}.",
+                name,
+                self.current_class_name.as_deref().unwrap_or("")
+            );
+        }
+
+        Ok(())
+    }
+
+    fn define_constant(
+        &mut self,
+        name: String,
+        value: i16,
+        variable_type: VariableType,
+    ) -> Result<()> {
+        if self
+            .class_symbol_table
+            .iter()
+            .any(|element| element.variable_name == name)
+        {
+            bail!(
+                "{} is already declared in class {}.",
+                name,
+                self.current_class_name.as_deref().unwrap_or("")
+            );
+        }
+
+        self.class_symbol_table.push(Variable {
+            variable_name: name,
+            variable_kind: VariableKind::Constant(value),
+            variable_type,
+            variable_scope: VariableScope::ClassLevel,
+            variable_index: 0,
+        });
+
+        Ok(())
+    }
+
     fn compile_type(&mut self) -> Result<()> {
-        // TODO: set current variable type here
         match &self.current_token {
             Some(Token::Keyword(Keyword::Int)) => {
                 self.compile_keyword(Keyword::Int)?;
+                self.current_variable_type = Some(VariableType::Integer);
             }
             Some(Token::Keyword(Keyword::Char)) => {
                 self.compile_keyword(Keyword::Char)?;
+                self.current_variable_type = Some(VariableType::Char);
             }
             Some(Token::Keyword(Keyword::Boolean)) => {
                 self.compile_keyword(Keyword::Boolean)?;
+                self.current_variable_type = Some(VariableType::Boolean);
             }
-            Some(Token::Identifier(_)) => {
+            Some(Token::Identifier(IdentifierValue { value })) => {
+                let class_name = value.clone();
+
+                // A class name used as a type, not a declaration of the current category.
+                let category = self
+                    .current_identifier_category
+                    .replace("class".to_string());
+                let usage = self.current_identifier_usage.replace("use".to_string());
+
                 self.compile_identifier()?;
+
+                self.current_identifier_category = category;
+                self.current_identifier_usage = usage;
+                self.referenced_classes.push(class_name.clone());
+                self.current_variable_type = Some(VariableType::ClassName(class_name));
             }
             _ => bail!(
                 "Expected to find type. But found this instead: {:?}.",
@@ -401,34 +843,65 @@ impl<'a> Compiler<'a> {
 
     // Action: Reset the subroutine level symbol table.
     fn compile_subroutinedec(&mut self) -> Result<()> {
+        self.subroutine_symbol_table.clear();
+        self.subroutine_local_count = 0;
+        self.block_start = 0;
+
         self.current_identifier_category = Some("subroutine".to_string());
         self.current_identifier_usage = Some("declaration".to_string());
 
         write!(self.output, "<subroutineDec>\n")?;
 
-        match &self.current_token {
+        let kind = match &self.current_token {
             Some(Token::Keyword(Keyword::Constructor)) => {
-                self.compile_keyword(Keyword::Constructor)?
+                self.compile_keyword(Keyword::Constructor)?;
+                SubroutineKind::Constructor
+            }
+            Some(Token::Keyword(Keyword::Function)) => {
+                self.compile_keyword(Keyword::Function)?;
+                SubroutineKind::Function
+            }
+            Some(Token::Keyword(Keyword::Method)) => {
+                self.compile_keyword(Keyword::Method)?;
+                SubroutineKind::Method
             }
-            Some(Token::Keyword(Keyword::Function)) => self.compile_keyword(Keyword::Function)?,
-            Some(Token::Keyword(Keyword::Method)) => self.compile_keyword(Keyword::Method)?,
             _ => bail!(
                 "Expected to find keyword 'constructor', 'function' or 'method'. But found this instead: {:?}.",
                 self.current_token
             ),
-        }
+        };
 
-        match &self.current_token {
-            Some(Token::Keyword(Keyword::Void)) => self.compile_keyword(Keyword::Void)?,
-            _ => self.compile_type()?,
-        }
+        let return_type = match &self.current_token {
+            Some(Token::Keyword(Keyword::Void)) => {
+                self.compile_keyword(Keyword::Void)?;
+                None
+            }
+            _ => {
+                self.compile_type()?;
+                self.current_variable_type
+                    .as_ref()
+                    .map(|return_type| return_type.name().to_string())
+            }
+        };
 
         self.compile_identifier()?;
+        self.current_subroutine_name = self.current_identifier_name.clone();
+        self.subroutines.push(SubroutineInfo {
+            name: self.current_subroutine_name.clone().unwrap_or_default(),
+            kind,
+            return_type,
+            local_count: 0,
+            calls: Vec::new(),
+        });
         self.compile_symbol(Symbol::LeftRoundBracket)?;
         self.compile_parameterlist()?;
         self.compile_symbol(Symbol::RightRoundBracket)?;
         self.compile_subroutinebody()?;
 
+        if let Some(subroutine) = self.subroutines.last_mut() {
+            subroutine.local_count = self.subroutine_local_count;
+        }
+
         self.current_identifier_category = None;
         self.current_identifier_usage = None;
 
@@ -439,6 +912,7 @@ impl<'a> Compiler<'a> {
     fn compile_parameterlist(&mut self) -> Result<()> {
         write!(self.output, "<parameterList>\n")?;
 
+        self.current_identifier_category = Some("argument".to_string());
         self.current_identifier_usage = Some("declaration".to_string());
 
         match self.compile_type() {
@@ -454,6 +928,7 @@ impl<'a> Compiler<'a> {
             Err(_) => {}
         };
 
+        self.current_identifier_category = None;
         self.current_identifier_usage = None;
 
         write!(self.output, "</parameterList>\n")?;
@@ -475,6 +950,8 @@ impl<'a> Compiler<'a> {
             self.compile_statement()?;
         }
 
+        self.check_extension_keyword()?;
+
         write!(self.output, "</statements>\n")?;
 
         self.compile_symbol(Symbol::RightCurlyBracket)?;
@@ -528,6 +1005,26 @@ impl<'a> Compiler<'a> {
             Some(Token::Keyword(Keyword::Return)) =>{
                 self.compile_return_statement()?;
             }
+            Some(Token::Keyword(Keyword::For)) => {
+                self.compile_for_statement()?;
+            }
+            Some(Token::Keyword(Keyword::Break)) => {
+                self.compile_break_statement()?;
+            }
+            Some(Token::Keyword(Keyword::Continue)) => {
+                self.compile_continue_statement()?;
+            }
+            Some(Token::Keyword(Keyword::Switch)) => {
+                self.compile_switch_statement()?;
+            }
+            Some(Token::Keyword(Keyword::Var)) => {
+                self.require_extension(
+                    Extension::BlockVariables,
+                    "'var' after the first statement or inside a block",
+                )?;
+
+                self.compile_vardec()?;
+            }
             _ => bail!(
                 "Expected to find 'let', 'if', 'while', 'do', or 'return'. But found this instead: {:?}.",
                 self.current_token
@@ -537,30 +1034,125 @@ impl<'a> Compiler<'a> {
         Ok(())
     }
 
+    // The statements of a block: the body of an if, else, while, for or switch clause. Variables
+    // declared in the block (--ext block-variables) go out of scope at its end.
+    fn compile_block_statements(&mut self) -> Result<()> {
+        write!(self.output, "<statements>\n")?;
+
+        let outer_block_start = self.block_start;
+        self.block_start = self.subroutine_symbol_table.len();
+
+        while self.is_statement() {
+            self.compile_statement()?;
+        }
+
+        self.check_extension_keyword()?;
+
+        self.subroutine_symbol_table.truncate(self.block_start);
+        self.block_start = outer_block_start;
+
+        write!(self.output, "</statements>\n")?;
+        Ok(())
+    }
+
     fn compile_let_statement(&mut self) -> Result<()> {
         write!(self.output, "<letStatement>\n")?;
 
-        self.current_identifier_usage = Some("use".to_string());
-
         self.compile_keyword(Keyword::Let)?;
-        self.compile_identifier()?;
+        self.compile_assignment()?;
+        self.compile_symbol(Symbol::Semicolon)?;
+
+        write!(self.output, "</letStatement>\n")?;
+        Ok(())
+    }
+
+    // The `varName ('[' expression ']')? '=' expression` part of a let statement. Also used for
+    // the initialization and step of a for statement.
+    fn compile_assignment(&mut self) -> Result<()> {
+        if let (Some(_), Some(Token::Identifier(IdentifierValue { value }))) =
+            (self.current_constant(), &self.current_token)
+        {
+            bail!(
+                "Cannot assign to constant {} in {}.{}.",
+                value,
+                self.current_class_name.as_deref().unwrap_or(""),
+                self.current_subroutine_name.as_deref().unwrap_or("")
+            );
+        }
+
+        self.compile_variable()?;
 
         if self.current_token == Some(Token::Symbol(Symbol::LeftSquareBracket)) {
-            self.compile_symbol(Symbol::LeftSquareBracket)?;
-            self.compile_expression()?;
-            self.compile_symbol(Symbol::RightSquareBracket)?;
+            self.compile_subscripts()?;
         }
 
-        self.compile_symbol(Symbol::Equal)?;
-        self.compile_expression()?;
-        self.compile_symbol(Symbol::Semicolon)?;
+        match self.current_token {
+            Some(Token::Symbol(Symbol::PlusEqual))
+            | Some(Token::Symbol(Symbol::MinusEqual))
+            | Some(Token::Symbol(Symbol::AndEqual))
+            | Some(Token::Symbol(Symbol::OrEqual))
+            | Some(Token::Symbol(Symbol::Increment))
+            | Some(Token::Symbol(Symbol::Decrement)) => self.compile_compound_assignment()?,
+            // Without --ext compound-assignment the tokenizer reads `+=` and `++` as two symbols.
+            Some(Token::Symbol(Symbol::Plus))
+            | Some(Token::Symbol(Symbol::Minus))
+            | Some(Token::Symbol(Symbol::And))
+            | Some(Token::Symbol(Symbol::Or))
+                if !self.options.has_extension(Extension::CompoundAssignment) =>
+            {
+                self.require_compound_assignment()?
+            }
+            _ => {
+                self.compile_symbol(Symbol::Equal)?;
+                self.compile_expression()?;
+            }
+        }
 
-        self.current_identifier_usage = None;
+        Ok(())
+    }
+
+    // op= expression, or '++' / '--'. Shorthand for `x = x op expression` and `x = x + 1` /
+    // `x = x - 1` that names the target, and evaluates an array index, only once.
+    fn compile_compound_assignment(&mut self) -> Result<()> {
+        let increment = matches!(
+            self.current_token,
+            Some(Token::Symbol(Symbol::Increment)) | Some(Token::Symbol(Symbol::Decrement))
+        );
+
+        if let Some(Token::Symbol(symbol)) = &self.current_token {
+            write!(self.output, "<symbol> {:?} </symbol>\n", symbol)?;
+        }
+
+        self.advance()?;
+
+        if !increment {
+            self.compile_expression()?;
+        }
 
-        write!(self.output, "</letStatement>\n")?;
         Ok(())
     }
 
+    // Fails with the compound assignment diagnostic for an operator in place of the '=' of an
+    // assignment, which is how `x += 1` and `x++` read without the extension.
+    fn require_compound_assignment(&mut self) -> Result<()> {
+        let operator = match &self.current_token {
+            Some(Token::Symbol(symbol)) => symbol.text(),
+            _ => "",
+        };
+
+        let increment = matches!(operator, "+" | "-")
+            && matches!(self.peek_token(), Some(Token::Symbol(symbol)) if symbol.text() == operator);
+
+        self.require_extension(
+            Extension::CompoundAssignment,
+            &if increment {
+                format!("'{}'", operator.repeat(2))
+            } else {
+                format!("'{}='", operator)
+            },
+        )
+    }
+
     fn compile_if_statement(&mut self) -> Result<()> {
         write!(self.output, "<ifStatement>\n")?;
 
@@ -570,29 +1162,27 @@ impl<'a> Compiler<'a> {
         self.compile_symbol(Symbol::RightRoundBracket)?;
         self.compile_symbol(Symbol::LeftCurlyBracket)?;
 
-        write!(self.output, "<statements>\n")?;
-
-        while self.is_statement() {
-            self.compile_statement()?;
-        }
-
-        write!(self.output, "</statements>\n")?;
+        self.compile_block_statements()?;
 
         self.compile_symbol(Symbol::RightCurlyBracket)?;
 
         if self.current_token == Some(Token::Keyword(Keyword::Else)) {
             self.compile_keyword(Keyword::Else)?;
-            self.compile_symbol(Symbol::LeftCurlyBracket)?;
 
-            write!(self.output, "<statements>\n")?;
+            // `else if` is sugar for an else branch holding just the nested if statement.
+            if self.current_token == Some(Token::Keyword(Keyword::If)) {
+                self.require_extension(Extension::ElseIf, "'else if'")?;
 
-            while self.is_statement() {
-                self.compile_statement()?;
-            }
+                write!(self.output, "<statements>\n")?;
+                self.compile_if_statement()?;
+                write!(self.output, "</statements>\n")?;
+            } else {
+                self.compile_symbol(Symbol::LeftCurlyBracket)?;
 
-            write!(self.output, "</statements>\n")?;
+                self.compile_block_statements()?;
 
-            self.compile_symbol(Symbol::RightCurlyBracket)?;
+                self.compile_symbol(Symbol::RightCurlyBracket)?;
+            }
         }
 
         write!(self.output, "</ifStatement>\n")?;
@@ -608,17 +1198,187 @@ impl<'a> Compiler<'a> {
         self.compile_symbol(Symbol::RightRoundBracket)?;
         self.compile_symbol(Symbol::LeftCurlyBracket)?;
 
-        write!(self.output, "<statements>\n")?;
+        self.loop_depth += 1;
+        self.break_depth += 1;
+        self.compile_block_statements()?;
+        self.break_depth -= 1;
+        self.loop_depth -= 1;
 
-        while self.is_statement() {
-            self.compile_statement()?;
+        self.compile_symbol(Symbol::RightCurlyBracket)?;
+
+        write!(self.output, "</whileStatement>\n")?;
+        Ok(())
+    }
+
+    // for '(' assignment? ';' expression? ';' assignment? ')' '{' statements '}'
+    fn compile_for_statement(&mut self) -> Result<()> {
+        write!(self.output, "<forStatement>\n")?;
+
+        self.compile_keyword(Keyword::For)?;
+        self.compile_symbol(Symbol::LeftRoundBracket)?;
+
+        if self.current_token != Some(Token::Symbol(Symbol::Semicolon)) {
+            write!(self.output, "<assignment>\n")?;
+            self.compile_assignment()?;
+            write!(self.output, "</assignment>\n")?;
         }
 
-        write!(self.output, "</statements>\n")?;
+        self.compile_symbol(Symbol::Semicolon)?;
+
+        if self.is_expression() {
+            self.compile_expression()?;
+        }
+
+        self.compile_symbol(Symbol::Semicolon)?;
+
+        if self.current_token != Some(Token::Symbol(Symbol::RightRoundBracket)) {
+            write!(self.output, "<assignment>\n")?;
+            self.compile_assignment()?;
+            write!(self.output, "</assignment>\n")?;
+        }
+
+        self.compile_symbol(Symbol::RightRoundBracket)?;
+        self.compile_symbol(Symbol::LeftCurlyBracket)?;
+
+        self.loop_depth += 1;
+        self.break_depth += 1;
+        self.compile_block_statements()?;
+        self.break_depth -= 1;
+        self.loop_depth -= 1;
 
         self.compile_symbol(Symbol::RightCurlyBracket)?;
 
-        write!(self.output, "</whileStatement>\n")?;
+        write!(self.output, "</forStatement>\n")?;
+        Ok(())
+    }
+
+    // switch '(' expression ')' '{' ('case' caseLabel ':' statements)* ('default' ':' statements)? '}'
+    fn compile_switch_statement(&mut self) -> Result<()> {
+        write!(self.output, "<switchStatement>\n")?;
+
+        self.compile_keyword(Keyword::Switch)?;
+        self.compile_symbol(Symbol::LeftRoundBracket)?;
+        self.compile_expression()?;
+        self.compile_symbol(Symbol::RightRoundBracket)?;
+        self.compile_symbol(Symbol::LeftCurlyBracket)?;
+
+        let mut labels = Vec::new();
+
+        self.break_depth += 1;
+
+        while self.current_token == Some(Token::Keyword(Keyword::Case)) {
+            write!(self.output, "<caseClause>\n")?;
+
+            self.compile_keyword(Keyword::Case)?;
+
+            let label = self.compile_case_label()?;
+
+            if labels.contains(&label) {
+                bail!(
+                    "Duplicate case label {} in {}.{}.",
+                    label,
+                    self.current_class_name.as_deref().unwrap_or(""),
+                    self.current_subroutine_name.as_deref().unwrap_or("")
+                );
+            }
+
+            labels.push(label);
+
+            self.compile_symbol(Symbol::Colon)?;
+
+            self.compile_block_statements()?;
+            write!(self.output, "</caseClause>\n")?;
+        }
+
+        if self.current_token == Some(Token::Keyword(Keyword::Default)) {
+            write!(self.output, "<defaultClause>\n")?;
+
+            self.compile_keyword(Keyword::Default)?;
+            self.compile_symbol(Symbol::Colon)?;
+
+            self.compile_block_statements()?;
+            write!(self.output, "</defaultClause>\n")?;
+        }
+
+        self.break_depth -= 1;
+
+        self.compile_symbol(Symbol::RightCurlyBracket)?;
+
+        write!(self.output, "</switchStatement>\n")?;
+        Ok(())
+    }
+
+    // A case label is an integer constant, optionally negated, or a named constant. Returns its
+    // value.
+    fn compile_case_label(&mut self) -> Result<i32> {
+        if let Some(value) = self.current_constant() {
+            self.write_constant(value)?;
+            self.advance()?;
+            return Ok(value.into());
+        }
+
+        let negative = self.current_token == Some(Token::Symbol(Symbol::Minus));
+
+        if negative {
+            self.compile_symbol(Symbol::Minus)?;
+        }
+
+        let value = match &self.current_token {
+            Some(Token::IntegerConstant(IntegerConstantValue { value })) => i32::from(*value),
+            _ => bail!(
+                "Expected to find a constant case label. But found this instead: {:?}.",
+                self.current_token
+            ),
+        };
+
+        if value == i32::from(IntegerConstantValue::NEGATIVE_LIMIT) {
+            if !negative {
+                return Err(self.located(IntegerConstantValue::out_of_range("32768")));
+            }
+
+            self.require_extension(Extension::Literals, "-32768")?;
+        }
+
+        self.compile_integer_constant()?;
+
+        Ok(if negative { -value } else { value })
+    }
+
+    // Leaves the innermost loop or switch statement.
+    fn compile_break_statement(&mut self) -> Result<()> {
+        if self.break_depth == 0 {
+            bail!(
+                "'break' outside of a loop or switch in {}.{}.",
+                self.current_class_name.as_deref().unwrap_or(""),
+                self.current_subroutine_name.as_deref().unwrap_or("")
+            );
+        }
+
+        write!(self.output, "<breakStatement>\n")?;
+
+        self.compile_keyword(Keyword::Break)?;
+        self.compile_symbol(Symbol::Semicolon)?;
+
+        write!(self.output, "</breakStatement>\n")?;
+        Ok(())
+    }
+
+    // Starts the next iteration of the innermost loop, even from inside a switch statement.
+    fn compile_continue_statement(&mut self) -> Result<()> {
+        if self.loop_depth == 0 {
+            bail!(
+                "'continue' outside of a loop in {}.{}.",
+                self.current_class_name.as_deref().unwrap_or(""),
+                self.current_subroutine_name.as_deref().unwrap_or("")
+            );
+        }
+
+        write!(self.output, "<continueStatement>\n")?;
+
+        self.compile_keyword(Keyword::Continue)?;
+        self.compile_symbol(Symbol::Semicolon)?;
+
+        write!(self.output, "</continueStatement>\n")?;
         Ok(())
     }
 
@@ -656,9 +1416,21 @@ impl<'a> Compiler<'a> {
 
         self.compile_term()?;
 
-        while self.is_op() {
+        let mut previous_precedence = None;
+
+        while let Some(precedence) = self.op_precedence() {
+            if previous_precedence.is_some_and(|previous| precedence > previous) {
+                self.lint_precedence();
+            }
+
+            previous_precedence = Some(precedence);
+
             self.compile_op()?;
-            self.compile_term()?;
+
+            match self.options.precedence {
+                Precedence::Jack => self.compile_term()?,
+                Precedence::C => self.compile_operand(precedence)?,
+            }
         }
 
         write!(self.output, "</expression>\n")?;
@@ -666,12 +1438,43 @@ impl<'a> Compiler<'a> {
         Ok(())
     }
 
+    // Compiles the right operand of an operator with the given precedence (C precedence mode).
+    // Operators that bind tighter than that operator are grouped with the operand into a nested
+    // expression; the rest are left to the enclosing expression, which applies them from left to
+    // right.
+    fn compile_operand(&mut self, precedence: u8) -> Result<()> {
+        self.output.begin_capture();
+        let term = self.compile_term();
+        let term_output = self.output.end_capture();
+        term?;
+
+        if self.op_precedence().is_none_or(|next| next <= precedence) {
+            self.output.write_all(&term_output)?;
+            return Ok(());
+        }
+
+        self.lint_precedence();
+
+        write!(self.output, "<term>\n<expression>\n")?;
+        self.output.write_all(&term_output)?;
+
+        while let Some(next) = self.op_precedence().filter(|next| *next > precedence) {
+            self.compile_op()?;
+            self.compile_operand(next)?;
+        }
+
+        write!(self.output, "</expression>\n</term>\n")?;
+
+        Ok(())
+    }
+
     fn compile_term(&mut self) -> Result<()> {
         write!(self.output, "<term>\n")?;
 
-        self.current_identifier_usage = Some("use".to_string());
-
         match &self.current_token {
+            Some(Token::IntegerConstant(IntegerConstantValue {
+                value: IntegerConstantValue::NEGATIVE_LIMIT,
+            })) => return Err(self.located(IntegerConstantValue::out_of_range("32768"))),
             Some(Token::IntegerConstant(_value)) => {
                 self.compile_integer_constant()?;
             }
@@ -688,21 +1491,28 @@ impl<'a> Compiler<'a> {
                 self.compile_keyword(Keyword::Null)?;
             }
             Some(Token::Keyword(Keyword::This)) => {
-                self.compile_keyword(Keyword::This)?;
+                if self.peek_token() == Some(&Token::Symbol(Symbol::Dot)) {
+                    self.compile_subroutine_call()?;
+                } else {
+                    self.compile_keyword(Keyword::This)?;
+                }
             }
             Some(Token::Identifier(_value)) => {
-                if self.tokenizer.peek() == Some(&Token::Symbol(Symbol::LeftSquareBracket)) {
+                if self.peek_token() == Some(&Token::Symbol(Symbol::LeftSquareBracket)) {
+                    self.check_not_constant_use("isn't an array")?;
+
                     // Process array indexing
-                    self.compile_identifier()?;
-                    self.compile_symbol(Symbol::LeftSquareBracket)?;
-                    self.compile_expression()?;
-                    self.compile_symbol(Symbol::RightSquareBracket)?;
-                } else if self.tokenizer.peek() == Some(&Token::Symbol(Symbol::LeftRoundBracket)) {
+                    self.compile_variable()?;
+                    self.compile_subscripts()?;
+                } else if self.peek_token() == Some(&Token::Symbol(Symbol::LeftRoundBracket)) {
                     self.compile_subroutine_call()?;
-                } else if self.tokenizer.peek() == Some(&Token::Symbol(Symbol::Dot)) {
+                } else if self.peek_token() == Some(&Token::Symbol(Symbol::Dot)) {
                     self.compile_subroutine_call()?;
+                } else if let Some(value) = self.current_constant() {
+                    self.write_constant(value)?;
+                    self.advance()?;
                 } else {
-                    self.compile_identifier()?;
+                    self.compile_variable()?;
                 }
             }
             Some(Token::Symbol(Symbol::LeftRoundBracket)) => {
@@ -710,9 +1520,26 @@ impl<'a> Compiler<'a> {
                 self.compile_expression()?;
                 self.compile_symbol(Symbol::RightRoundBracket)?;
             }
+            Some(Token::Symbol(Symbol::LeftSquareBracket)) => {
+                self.compile_array_initializer()?;
+            }
             Some(Token::Symbol(Symbol::Minus)) => {
                 self.compile_symbol(Symbol::Minus)?;
-                self.compile_term()?;
+
+                // -32768 is the one place where 32768 is a valid integer constant.
+                if self.current_token
+                    == Some(Token::IntegerConstant(
+                        IntegerConstantValue::negative_limit(),
+                    ))
+                {
+                    self.require_extension(Extension::Literals, "-32768")?;
+
+                    write!(self.output, "<term>\n")?;
+                    self.compile_integer_constant()?;
+                    write!(self.output, "</term>\n")?;
+                } else {
+                    self.compile_term()?;
+                }
             }
             Some(Token::Symbol(Symbol::Not)) => {
                 self.compile_symbol(Symbol::Not)?;
@@ -724,31 +1551,169 @@ impl<'a> Compiler<'a> {
             ),
         }
 
-        self.current_identifier_usage = None;
-
         write!(self.output, "</term>\n")?;
         Ok(())
     }
 
+    // ('[' expression ']')+ after the name of an array. Each further subscript indexes the Array
+    // stored at the element selected so far, as in `grid[row][column]`.
+    fn compile_subscripts(&mut self) -> Result<()> {
+        self.compile_symbol(Symbol::LeftSquareBracket)?;
+        self.compile_expression()?;
+        self.compile_symbol(Symbol::RightSquareBracket)?;
+
+        while self.current_token == Some(Token::Symbol(Symbol::LeftSquareBracket)) {
+            self.require_extension(Extension::MultiDimensionalArrays, "chained array subscript")?;
+
+            self.compile_symbol(Symbol::LeftSquareBracket)?;
+            self.compile_expression()?;
+            self.compile_symbol(Symbol::RightSquareBracket)?;
+        }
+
+        Ok(())
+    }
+
+    // '[' expressionList ']': a new Array of that many elements, each initialized with the
+    // corresponding expression.
+    fn compile_array_initializer(&mut self) -> Result<()> {
+        self.require_extension(Extension::ArrayInitializers, "array initializer")?;
+
+        self.compile_symbol(Symbol::LeftSquareBracket)?;
+
+        if self.current_token == Some(Token::Symbol(Symbol::RightSquareBracket)) {
+            bail!(
+                "An array initializer needs at least one element ({}.{}).",
+                self.current_class_name.as_deref().unwrap_or(""),
+                self.current_subroutine_name.as_deref().unwrap_or("")
+            );
+        }
+
+        self.compile_expression_list()?;
+        self.compile_symbol(Symbol::RightSquareBracket)?;
+
+        Ok(())
+    }
+
+    // subroutineName '(' expressionList ')' or (className | varName) '.' subroutineName '('
+    // expressionList ')'. With --ext call-chains the receiver can also be `this`, and the call can
+    // be followed by more '.' subroutineName '(' expressionList ')' calls on its result.
     fn compile_subroutine_call(&mut self) -> Result<()> {
-        self.current_identifier_usage = Some("use".to_string());
+        // Class of the called subroutine, when it is known.
+        let mut class = Some(self.class_name().to_string());
+
+        if self.current_token == Some(Token::Keyword(Keyword::This)) {
+            self.require_extension(Extension::CallChains, "call through this")?;
+
+            // Only methods and constructors have an object.
+            match self.current_subroutine_kind() {
+                Some(SubroutineKind::Method | SubroutineKind::Constructor) => {}
+                Some(SubroutineKind::Function) => bail!(
+                    "{} is a function, so it has no 'this' to call methods on.",
+                    self.location()
+                ),
+                None => bail!(
+                    "The static initializers of {} run without an object, so they have no 'this' to call methods on.",
+                    self.class_name()
+                ),
+            }
 
-        self.compile_identifier()?;
+            self.compile_keyword(Keyword::This)?;
+            self.compile_symbol(Symbol::Dot)?;
+        } else if self.peek_token() == Some(&Token::Symbol(Symbol::Dot)) {
+            self.check_not_constant_use("has no methods to call")?;
+
+            let receiver = self.current_identifier()?;
+
+            class = self.receiver_class(&receiver)?;
+
+            if self.find_variable(&receiver).is_some() {
+                self.compile_variable()?;
+            } else {
+                self.referenced_classes.push(receiver);
+                self.compile_reference("class")?;
+            }
 
-        if self.current_token == Some(Token::Symbol(Symbol::Dot)) {
             self.compile_symbol(Symbol::Dot)?;
-            self.compile_identifier()?;
         }
 
+        self.compile_reference("subroutine")?;
+
+        let subroutine = self.current_identifier_name.clone().unwrap_or_default();
+
         self.compile_symbol(Symbol::LeftRoundBracket)?;
         self.compile_expression_list()?;
         self.compile_symbol(Symbol::RightRoundBracket)?;
 
-        self.current_identifier_usage = None;
+        let mut call = self.record_call(class, subroutine, None);
+
+        while self.current_token == Some(Token::Symbol(Symbol::Dot)) {
+            self.require_extension(Extension::CallChains, "call on the result of a call")?;
+
+            self.compile_symbol(Symbol::Dot)?;
+            self.compile_reference("subroutine")?;
+
+            let subroutine = self.current_identifier_name.clone().unwrap_or_default();
+
+            self.compile_symbol(Symbol::LeftRoundBracket)?;
+            self.compile_expression_list()?;
+            self.compile_symbol(Symbol::RightRoundBracket)?;
+
+            // Its class is the return type of the call it is made on, which is resolved once the
+            // whole program is compiled.
+            call = self.record_call(None, subroutine, call);
+        }
 
         Ok(())
     }
 
+    // Class whose subroutine `receiver.name(...)` calls: the type of the variable `receiver`, or
+    // `receiver` itself when it is a class name. With --ext call-chains, where the class of a
+    // call matters for the calls chained to it, a variable of a primitive type is an error;
+    // otherwise its class is just unknown.
+    fn receiver_class(&self, receiver: &str) -> Result<Option<String>> {
+        match self
+            .find_variable(receiver)
+            .map(|variable| &variable.variable_type)
+        {
+            Some(VariableType::ClassName(class)) => Ok(Some(class.clone())),
+            Some(variable_type) if self.options.has_extension(Extension::CallChains) => bail!(
+                "{} is of type {} in {}, so it has no methods to call.",
+                receiver,
+                variable_type.name(),
+                self.location()
+            ),
+            Some(_) => Ok(None),
+            None => Ok(Some(receiver.to_string())),
+        }
+    }
+
+    // Adds a call, once its arguments are compiled, to the calls of the current subroutine, or
+    // of the static initializers outside subroutines, and returns its index there.
+    fn record_call(
+        &mut self,
+        class: Option<String>,
+        subroutine: String,
+        on_result_of: Option<usize>,
+    ) -> Option<usize> {
+        let calls = match self.current_subroutine_name {
+            Some(_) => &mut self.subroutines.last_mut()?.calls,
+            None => &mut self.static_initializer_calls,
+        };
+
+        calls.push(CallInfo {
+            class,
+            subroutine,
+            on_result_of,
+        });
+
+        Some(calls.len() - 1)
+    }
+
+    fn current_subroutine_kind(&self) -> Option<SubroutineKind> {
+        self.current_subroutine_name.as_ref()?;
+        self.subroutines.last().map(|subroutine| subroutine.kind)
+    }
+
     fn compile_expression_list(&mut self) -> Result<()> {
         write!(self.output, "<expressionList>\n")?;
 
@@ -785,6 +1750,248 @@ impl<'a> Compiler<'a> {
         Ok(())
     }
 
+    // Constant expressions
+
+    // Evaluates a constant expression with 16-bit Hack arithmetic, consuming its tokens without
+    // writing them. Binary operators follow the --precedence mode, like ordinary expressions.
+    fn evaluate_constant_expression(&mut self) -> Result<i16> {
+        self.evaluate_constant_operation(0)
+    }
+
+    fn evaluate_constant_operation(&mut self, min_precedence: u8) -> Result<i16> {
+        let mut value = self.evaluate_constant_term()?;
+
+        while let Some(precedence) = self.op_precedence() {
+            let precedence = match self.options.precedence {
+                Precedence::Jack => 1,
+                Precedence::C => precedence,
+            };
+
+            if precedence < min_precedence {
+                break;
+            }
+
+            let operator = self.current_token.take();
+            self.advance()?;
+
+            let operand = self.evaluate_constant_operation(precedence + 1)?;
+
+            value = match operator {
+                Some(Token::Symbol(Symbol::Plus)) => value.wrapping_add(operand),
+                Some(Token::Symbol(Symbol::Minus)) => value.wrapping_sub(operand),
+                Some(Token::Symbol(Symbol::Times)) => value.wrapping_mul(operand),
+                Some(Token::Symbol(Symbol::Divide)) => match operand {
+                    0 => bail!("Division by zero in constant expression."),
+                    _ => value.wrapping_div(operand),
+                },
+                Some(Token::Symbol(Symbol::And)) => value & operand,
+                Some(Token::Symbol(Symbol::Or)) => value | operand,
+                Some(Token::Symbol(Symbol::SmallerThan)) => -i16::from(value < operand),
+                Some(Token::Symbol(Symbol::GreaterThan)) => -i16::from(value > operand),
+                _ => -i16::from(value == operand),
+            };
+        }
+
+        Ok(value)
+    }
+
+    fn evaluate_constant_term(&mut self) -> Result<i16> {
+        if let Some(value) = self.current_constant() {
+            self.advance()?;
+            return Ok(value);
+        }
+
+        let value = match &self.current_token {
+            Some(Token::IntegerConstant(IntegerConstantValue {
+                value: IntegerConstantValue::NEGATIVE_LIMIT,
+            })) => return Err(self.located(IntegerConstantValue::out_of_range("32768"))),
+            Some(Token::IntegerConstant(IntegerConstantValue { value })) => *value as i16,
+            Some(Token::Keyword(Keyword::True)) => -1,
+            Some(Token::Keyword(Keyword::False)) => 0,
+            Some(Token::Keyword(Keyword::Null)) => 0,
+            Some(Token::Symbol(Symbol::LeftRoundBracket)) => {
+                self.advance()?;
+
+                let value = self.evaluate_constant_expression()?;
+
+                if self.current_token != Some(Token::Symbol(Symbol::RightRoundBracket)) {
+                    bail!(
+                        "Expected to find symbol ). But found this instead: {:?}.",
+                        self.current_token
+                    );
+                }
+
+                value
+            }
+            Some(Token::Symbol(Symbol::Minus)) => {
+                self.advance()?;
+
+                if self.current_token
+                    == Some(Token::IntegerConstant(
+                        IntegerConstantValue::negative_limit(),
+                    ))
+                {
+                    self.require_extension(Extension::Literals, "-32768")?;
+
+                    self.advance()?;
+                    return Ok(i16::MIN);
+                }
+
+                return Ok(self.evaluate_constant_term()?.wrapping_neg());
+            }
+            Some(Token::Symbol(Symbol::Not)) => {
+                self.advance()?;
+                return Ok(!self.evaluate_constant_term()?);
+            }
+            _ => bail!(
+                "Expected to find a constant term. But found this instead: {:?}.",
+                self.current_token
+            ),
+        };
+
+        self.advance()?;
+        Ok(value)
+    }
+
+    // Value of the current token if it names a constant.
+    fn current_constant(&self) -> Option<i16> {
+        let name = match &self.current_token {
+            Some(Token::Identifier(IdentifierValue { value })) => value,
+            _ => return None,
+        };
+
+        // Local variables shadow constants of the same name.
+        if self
+            .subroutine_symbol_table
+            .iter()
+            .any(|element| &element.variable_name == name)
+        {
+            return None;
+        }
+
+        match self
+            .class_symbol_table
+            .iter()
+            .rev()
+            .find(|element| &element.variable_name == name)
+        {
+            Some(Variable {
+                variable_kind: VariableKind::Constant(value),
+                ..
+            }) => Some(*value),
+            _ => None,
+        }
+    }
+
+    // Fails if the current token names a constant, which is used in a way that only a variable
+    // or a class allows; `problem` says what is wrong with that.
+    fn check_not_constant_use(&self, problem: &str) -> Result<()> {
+        if let (Some(_), Some(Token::Identifier(IdentifierValue { value }))) =
+            (self.current_constant(), &self.current_token)
+        {
+            bail!(
+                "{} is a constant in {}.{}, so it {}.",
+                value,
+                self.class_name(),
+                self.current_subroutine_name.as_deref().unwrap_or(""),
+                problem
+            );
+        }
+
+        Ok(())
+    }
+
+    // The variable `name` in scope: the innermost declaration in the subroutine, or else the
+    // class variable. None if no variable has that name.
+    fn find_variable(&self, name: &str) -> Option<&Variable> {
+        self.subroutine_symbol_table
+            .iter()
+            .rev()
+            .chain(self.class_symbol_table.iter().rev())
+            .find(|element| {
+                element.variable_name == name
+                    && !matches!(element.variable_kind, VariableKind::Constant(_))
+            })
+    }
+
+    // Writes the value of a constant where a term is expected: an integer constant, negated when
+    // the value is negative.
+    fn write_constant(&mut self, value: i16) -> Result<()> {
+        if value < 0 {
+            write!(self.output, "<symbol> - </symbol>\n<term>\n")?;
+            write!(
+                self.output,
+                "<integerConstant> {} </integerConstant>\n",
+                value.unsigned_abs()
+            )?;
+            write!(self.output, "</term>\n")?;
+        } else {
+            write!(
+                self.output,
+                "<integerConstant> {} </integerConstant>\n",
+                value
+            )?;
+        }
+
+        Ok(())
+    }
+
+    // Called when the current operator binds tighter than the one before it, so that
+    // left-to-right evaluation and C precedence give the expression different meanings.
+    fn lint_precedence(&mut self) {
+        if !self.options.lint_precedence {
+            return;
+        }
+
+        let warning = format!(
+            "{}.{}: operator '{}' follows a lower precedence operator without parentheses; its meaning differs between --precedence=jack and --precedence=c",
+            self.current_class_name.as_deref().unwrap_or(""),
+            self.current_subroutine_name.as_deref().unwrap_or(""),
+            match &self.current_token {
+                Some(Token::Symbol(symbol)) => symbol.text(),
+                _ => "",
+            }
+        );
+
+        self.warnings.push(warning);
+    }
+
+    // Fails with a diagnostic naming the feature unless `extension` is enabled. `construct` is
+    // what was used; the diagnostic adds where.
+    fn require_extension(&self, extension: Extension, construct: &str) -> Result<()> {
+        self.options
+            .require_extension(extension, &format!("{} in {}", construct, self.location()))
+    }
+
+    // Where the compiler is, for diagnostics: Class.subroutine, or Class outside subroutines.
+    fn location(&self) -> String {
+        match &self.current_subroutine_name {
+            Some(subroutine_name) => format!("{}.{}", self.class_name(), subroutine_name),
+            None => self.class_name().to_string(),
+        }
+    }
+
+    // A diagnostic of the tokenizer, with where the compiler is, as in "Main.main: unterminated
+    // string constant". Before the class name is known there is nothing to add.
+    fn located(&self, error: anyhow::Error) -> anyhow::Error {
+        match &self.current_class_name {
+            Some(_) => anyhow!("{}: {}", self.location(), error),
+            None => error,
+        }
+    }
+
+    // Called where standard Jack doesn't allow an identifier. The tokenizer leaves the keywords of
+    // disabled extensions as identifiers, so one found here is reported as that extension.
+    fn check_extension_keyword(&self) -> Result<()> {
+        if let Some(Token::Identifier(IdentifierValue { value })) = &self.current_token {
+            if let Some((_, extension)) = keyword_extension(value) {
+                self.require_extension(extension, &format!("'{}'", value))?;
+            }
+        }
+
+        Ok(())
+    }
+
     // Utilitites
     fn is_expression(&self) -> bool {
         match &self.current_token {
@@ -796,6 +2003,7 @@ impl<'a> Compiler<'a> {
             Some(Token::Keyword(Keyword::This)) => true,
             Some(Token::Identifier(_value)) => true,
             Some(Token::Symbol(Symbol::LeftRoundBracket)) => true,
+            Some(Token::Symbol(Symbol::LeftSquareBracket)) => true,
             Some(Token::Symbol(Symbol::Minus)) => true,
             Some(Token::Symbol(Symbol::Not)) => true,
             _ => false,
@@ -809,22 +2017,29 @@ impl<'a> Compiler<'a> {
             Some(Token::Keyword(Keyword::While)) => true,
             Some(Token::Keyword(Keyword::Do)) => true,
             Some(Token::Keyword(Keyword::Return)) => true,
+            Some(Token::Keyword(Keyword::For)) => true,
+            Some(Token::Keyword(Keyword::Break)) => true,
+            Some(Token::Keyword(Keyword::Continue)) => true,
+            Some(Token::Keyword(Keyword::Switch)) => true,
+            Some(Token::Keyword(Keyword::Var)) => true,
             _ => false,
         }
     }
 
-    fn is_op(&self) -> bool {
+    // Precedence of the current token if it is a binary operator, following C: a higher value
+    // binds tighter.
+    fn op_precedence(&self) -> Option<u8> {
         match self.current_token {
-            Some(Token::Symbol(Symbol::Plus)) => true,
-            Some(Token::Symbol(Symbol::Minus)) => true,
-            Some(Token::Symbol(Symbol::Times)) => true,
-            Some(Token::Symbol(Symbol::Divide)) => true,
-            Some(Token::Symbol(Symbol::And)) => true,
-            Some(Token::Symbol(Symbol::Or)) => true,
-            Some(Token::Symbol(Symbol::SmallerThan)) => true,
-            Some(Token::Symbol(Symbol::GreaterThan)) => true,
-            Some(Token::Symbol(Symbol::Equal)) => true,
-            _ => false,
+            Some(Token::Symbol(Symbol::Times)) => Some(6),
+            Some(Token::Symbol(Symbol::Divide)) => Some(6),
+            Some(Token::Symbol(Symbol::Plus)) => Some(5),
+            Some(Token::Symbol(Symbol::Minus)) => Some(5),
+            Some(Token::Symbol(Symbol::SmallerThan)) => Some(4),
+            Some(Token::Symbol(Symbol::GreaterThan)) => Some(4),
+            Some(Token::Symbol(Symbol::Equal)) => Some(3),
+            Some(Token::Symbol(Symbol::And)) => Some(2),
+            Some(Token::Symbol(Symbol::Or)) => Some(1),
+            _ => None,
         }
     }
 }
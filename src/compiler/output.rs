@@ -0,0 +1,39 @@
+use std::io::{Result, Write};
+
+// Destination of the compiler output. Writes go to the underlying writer unless a capture is
+// active, in which case they are buffered so the caller can decide later where (and inside which
+// elements) they end up. Captures can be nested.
+pub struct Output<'a> {
+    writer: &'a mut dyn Write,
+    captures: Vec<Vec<u8>>,
+}
+
+impl<'a> Output<'a> {
+    pub fn new(writer: &'a mut dyn Write) -> Self {
+        Output {
+            writer,
+            captures: Vec::new(),
+        }
+    }
+
+    pub fn begin_capture(&mut self) {
+        self.captures.push(Vec::new());
+    }
+
+    pub fn end_capture(&mut self) -> Vec<u8> {
+        self.captures.pop().unwrap_or_default()
+    }
+}
+
+impl<'a> Write for Output<'a> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        match self.captures.last_mut() {
+            Some(capture) => capture.write(buf),
+            None => self.writer.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.writer.flush()
+    }
+}
@@ -16,7 +16,7 @@ pub struct Variable {
     pub variable_index: usize,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum VariableType {
     Integer,
     Boolean,
@@ -24,12 +24,38 @@ pub enum VariableType {
     ClassName(String),
 }
 
+impl VariableType {
+    pub fn name(&self) -> &str {
+        match self {
+            VariableType::Integer => "int",
+            VariableType::Boolean => "boolean",
+            VariableType::Char => "char",
+            VariableType::ClassName(class_name) => class_name,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum VariableKind {
     Static,
     Field,
     Variable,
     Argument,
+    // Compile-time constant with its value; it is substituted wherever it's used.
+    Constant(i16),
+}
+
+impl VariableKind {
+    // Category of the variable in the compiler output.
+    pub fn name(&self) -> &str {
+        match self {
+            VariableKind::Static => "static",
+            VariableKind::Field => "field",
+            VariableKind::Variable => "local",
+            VariableKind::Argument => "argument",
+            VariableKind::Constant(_) => "constant",
+        }
+    }
 }
 
 #[derive(Debug)]
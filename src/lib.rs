@@ -1,7 +1,14 @@
-use anyhow::Result;
-use clap::Parser;
+use anyhow::{anyhow, bail, Result};
+use clap::{Args, Parser, ValueEnum};
 use compiler::Compiler;
-use std::{fs::File, io::BufReader};
+use std::{
+    collections::BTreeMap,
+    env,
+    ffi::OsStr,
+    fmt, fs,
+    io::{self, BufRead, Write},
+    path::{Path, PathBuf},
+};
 use tokenizer::Tokenizer;
 use walkdir::WalkDir;
 
@@ -10,30 +17,753 @@ mod tokenizer;
 
 #[derive(Parser, Debug)]
 pub struct Config {
-    #[arg(help = "Input file or directory.")]
-    file_path: String,
+    #[arg(
+        required = true,
+        help = "Input files or directories. The files given and those directly in the directories given make up one program; each subdirectory is a program of its own."
+    )]
+    file_paths: Vec<String>,
+
+    #[arg(
+        short = 'L',
+        long = "library",
+        value_name = "DIR",
+        help = "Directory to search for classes the program uses but doesn't define. Can be given multiple times. Directories in JACKPATH are searched after these."
+    )]
+    library_paths: Vec<String>,
+
+    #[command(flatten)]
+    options: Options,
+}
+
+#[derive(Args, Clone, Debug, Default)]
+pub struct Options {
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = Precedence::Jack,
+        help = "Operator precedence used to parse expressions."
+    )]
+    pub precedence: Precedence,
+
+    #[arg(
+        long,
+        help = "Warn about expressions whose meaning depends on the precedence mode."
+    )]
+    pub lint_precedence: bool,
+
+    #[arg(
+        long,
+        value_enum,
+        help = "Language edition. Strict is standard Jack only and can't be combined with --ext or --precedence c; extended enables every --ext feature. Without it, standard Jack plus the --ext features given."
+    )]
+    pub edition: Option<Edition>,
+
+    #[arg(
+        long = "ext",
+        value_enum,
+        help = "Enable a language extension. Can be given multiple times."
+    )]
+    pub extensions: Vec<Extension>,
+}
+
+impl Options {
+    pub fn has_extension(&self, extension: Extension) -> bool {
+        self.edition == Some(Edition::Extended) || self.extensions.contains(&extension)
+    }
+
+    // Fails unless `extension` is enabled, naming the feature and how to enable it. `usage` says
+    // what was used and where, for example "'for' in Main.main".
+    pub fn require_extension(&self, extension: Extension, usage: &str) -> Result<()> {
+        if self.has_extension(extension) {
+            return Ok(());
+        }
+
+        Err(MissingExtension {
+            extension,
+            usage: usage.to_string(),
+        }
+        .into())
+    }
+
+    // Fails if the options contradict each other: --edition strict asks for standard Jack only.
+    pub fn check(&self) -> Result<()> {
+        if self.edition != Some(Edition::Strict) {
+            return Ok(());
+        }
+
+        if let Some(extension) = self.extensions.first() {
+            bail!(
+                "--ext {} can't be combined with --edition strict, which only allows standard Jack.",
+                extension.name()
+            );
+        }
+
+        if self.precedence == Precedence::C {
+            bail!("--precedence c can't be combined with --edition strict, which only allows standard Jack: it changes the meaning of standard programs.");
+        }
+
+        Ok(())
+    }
+}
+
+// Error for a feature of a disabled extension. The tokenizer doesn't know where in the class it
+// is, so the compiler adds that (see Compiler::advance).
+#[derive(Debug)]
+struct MissingExtension {
+    extension: Extension,
+    usage: String,
+}
+
+impl fmt::Display for MissingExtension {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Feature {} ({}) is not part of standard Jack. Enable it with --ext {} or --edition extended.",
+            self.extension.name(),
+            self.usage,
+            self.extension.name()
+        )
+    }
+}
+
+impl std::error::Error for MissingExtension {}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum Extension {
+    // Hexadecimal (0x7FFF) and binary (0b1010) integer constants, character literals ('a',
+    // '\n') standing for their Hack character codes, and -32768, whose magnitude is one more
+    // than the largest standard integer constant.
+    Literals,
+    // Escape sequences in string constants: \", \\, \n (Hack newline, 128), \t (four spaces)
+    // and \xHH for any Hack character code.
+    StringEscapes,
+    // `for (init; condition; step) { ... }` loops, and `break` and `continue` inside `for` and
+    // `while` loops. `break` also leaves a switch statement.
+    Loops,
+    // `else if (...) { ... }` without braces around the nested if statement.
+    ElseIf,
+    // `switch (expression) { case 81: ... default: ... }` with constant case labels. Cases don't
+    // fall through. As in C, `break` (--ext loops) inside a case leaves the switch, not an
+    // enclosing loop; `continue` still applies to the loop.
+    Switch,
+    // Class-level `const int NAME = <constant expression>;` and `enum Name { A, B = 5, C }`
+    // declarations. Constants can also be char, or boolean with the value true or false. They
+    // are substituted at compile time and take no storage.
+    Constants,
+    // `let x += e;` and likewise -=, &= and |=, plus `let x++;` and `let x--;`. Also allowed in
+    // the step of a for loop. Each operator is a single token, so as in C `a--b` needs a space
+    // to mean `a - -b`.
+    CompoundAssignment,
+    // `var` declarations anywhere a statement is allowed, scoped to the enclosing block.
+    BlockVariables,
+    // `[1, 2, 3]` as a term: a new Array holding the values of the expressions.
+    ArrayInitializers,
+    // `static int x = 5;`. The initializers of all classes run at the start of Sys.init (or
+    // Main.main, when there's no Sys class), which must be a function. A class's initializers run
+    // after those of the classes they use; compile_program reports the order.
+    StaticInitializers,
+    // `this.method()` in methods and constructors, and calls on the result of a call:
+    // `getBall().move()`, `a.b().c()`. The class of each call is reported by compile_program.
+    CallChains,
+    // `grid[r][c]` in expressions and let targets, indexing an Array of Arrays.
+    MultiDimensionalArrays,
+}
+
+impl Extension {
+    // The name given to --ext.
+    pub fn name(&self) -> String {
+        self.to_possible_value()
+            .map(|value| value.get_name().to_string())
+            .unwrap_or_default()
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum Edition {
+    // The grammar of the nand2tetris book, without any extension or --precedence c.
+    Strict,
+    // Every extension is enabled.
+    Extended,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq)]
+pub enum Precedence {
+    // Standard Jack: binary operators are applied strictly from left to right.
+    #[default]
+    Jack,
+    // C-like precedence and left associativity: * / before + - before < > before = before &
+    // before |.
+    C,
 }
 
 pub fn run(config: Config) -> Result<()> {
-    for entry in WalkDir::new(config.file_path) {
+    let library_paths = library_paths(&config)?;
+
+    for paths in programs(&config.file_paths)? {
+        run_program(paths, &library_paths, &config.options)?;
+    }
+
+    return Ok(());
+}
+
+// Compiles one program, linking in the library classes it uses, and writes Foo.xml next to each
+// Foo.jack.
+fn run_program(
+    mut paths: Vec<PathBuf>,
+    library_paths: &[PathBuf],
+    options: &Options,
+) -> Result<()> {
+    let mut sources = Vec::new();
+
+    for path in &paths {
+        sources.push(fs::read_to_string(path)?);
+    }
+
+    // Classes linked in from a library come after these. Their output goes next to the program's
+    // own, not into the library.
+    let program_count = paths.len();
+
+    if !library_paths.is_empty() {
+        link_libraries(&mut paths, &mut sources, library_paths, options)?;
+    }
+
+    let compilation = compile_program(&sources, options)?;
+
+    for warning in &compilation.warnings {
+        eprintln!("warning: {}", warning);
+    }
+
+    let outputs = compilation.outputs;
+
+    let output_directory = match paths.first() {
+        Some(path) => path.parent().unwrap_or(Path::new(".")).to_path_buf(),
+        None => PathBuf::from("."),
+    };
+
+    for (index, (path, output)) in paths.iter().zip(outputs).enumerate() {
+        let out_file_path = if index < program_count {
+            path.with_extension("xml")
+        } else {
+            output_directory.join(path.with_extension("xml").file_name().unwrap_or_default())
+        };
+
+        fs::write(out_file_path, output)?;
+    }
+
+    if !library_paths.is_empty() {
+        println!("Linked classes of {}:", output_directory.display());
+
+        for path in &paths {
+            println!(
+                "  {} ({})",
+                path.file_stem().unwrap_or_default().to_string_lossy(),
+                path.display()
+            );
+        }
+    }
+
+    Ok(())
+}
+
+// The programs named on the command line. The files given and those directly in the directories
+// given are one program, so a class may be defined only once among them. Every subdirectory is a
+// program of its own, as when compiling a directory of projects.
+fn programs(file_paths: &[String]) -> Result<Vec<Vec<PathBuf>>> {
+    let mut roots: Vec<PathBuf> = Vec::new();
+    let mut subdirectories: BTreeMap<PathBuf, Vec<PathBuf>> = BTreeMap::new();
+
+    for file_path in file_paths {
+        let root = Path::new(file_path);
+
+        for path in jack_files(root, None)? {
+            let directory = path.parent().unwrap_or(Path::new(".")).to_path_buf();
+
+            let program = if path == root || directory == root {
+                &mut roots
+            } else {
+                subdirectories.entry(directory).or_default()
+            };
+
+            if !program.contains(&path) {
+                program.push(path);
+            }
+        }
+    }
+
+    for (index, path) in roots.iter().enumerate() {
+        if let Some(other) = roots[..index]
+            .iter()
+            .find(|other| other.file_stem() == path.file_stem())
+        {
+            bail!(
+                "Class {} is defined in both {} and {}.",
+                path.file_stem().unwrap_or_default().to_string_lossy(),
+                other.display(),
+                path.display()
+            );
+        }
+    }
+
+    let mut programs = Vec::new();
+
+    if !roots.is_empty() {
+        programs.push(roots.clone());
+    }
+
+    // A directory given on the command line is part of the first program even if it is also
+    // below another one.
+    for mut paths in subdirectories.into_values() {
+        paths.retain(|path| !roots.contains(path));
+
+        if !paths.is_empty() {
+            programs.push(paths);
+        }
+    }
+
+    Ok(programs)
+}
+
+// The directories to link classes from: those given with -L, then those in JACKPATH.
+fn library_paths(config: &Config) -> Result<Vec<PathBuf>> {
+    let mut library_paths = Vec::new();
+
+    for library_path in &config.library_paths {
+        let library_path = PathBuf::from(library_path);
+
+        if !library_path.is_dir() {
+            bail!(
+                "Library directory {} given with -L doesn't exist or isn't a directory.",
+                library_path.display()
+            );
+        }
+
+        library_paths.push(library_path);
+    }
+
+    if let Some(jack_path) = env::var_os("JACKPATH") {
+        for library_path in env::split_paths(&jack_path) {
+            if library_path.as_os_str().is_empty() {
+                continue;
+            }
+
+            if !library_path.is_dir() {
+                eprintln!(
+                    "warning: JACKPATH entry {} doesn't exist or isn't a directory, so it is skipped.",
+                    library_path.display()
+                );
+                continue;
+            }
+
+            library_paths.push(library_path);
+        }
+    }
+
+    Ok(library_paths)
+}
+
+// The .jack files at `path`, sorted: the file itself, or those in the directory and its
+// subdirectories up to `max_depth`.
+fn jack_files(path: &Path, max_depth: Option<usize>) -> Result<Vec<PathBuf>> {
+    let mut walk_dir = WalkDir::new(path);
+
+    if let Some(max_depth) = max_depth {
+        walk_dir = walk_dir.max_depth(max_depth);
+    }
+
+    let mut paths = Vec::new();
+
+    for entry in walk_dir {
         let entry = entry?;
 
         if let Some(extension) = entry.path().extension() {
             if extension == "jack" {
-                let in_file_path = entry.path().to_string_lossy().to_string();
-                let in_file = File::open(in_file_path)?;
-                let mut buf_reader = BufReader::new(in_file);
+                paths.push(entry.into_path());
+            }
+        }
+    }
+
+    paths.sort();
+
+    Ok(paths)
+}
+
+// Adds to the program every class it uses, directly or through other library classes, that one
+// of the library directories defines. The first directory that defines a class wins. Classes the
+// libraries don't define either, such as those of the Jack OS, are left for the VM to provide.
+fn link_libraries(
+    paths: &mut Vec<PathBuf>,
+    sources: &mut Vec<String>,
+    library_paths: &[PathBuf],
+    options: &Options,
+) -> Result<()> {
+    let mut library = Vec::new();
+
+    for library_path in library_paths {
+        library.extend(jack_files(library_path, Some(1))?);
+    }
+
+    let mut index = 0;
 
-                let mut tokenizer = Tokenizer::new(&mut buf_reader).peekable();
+    while index < sources.len() {
+        // The path is part of the message, so that printing it doesn't drop the diagnostic.
+        let summary = compile_class(&mut sources[index].as_bytes(), &mut io::sink(), options)
+            .map_err(|error| anyhow!("{}: {:#}", paths[index].display(), error))?;
 
-                let out_file_path = entry.path().with_extension("xml");
-                let mut out_file = File::create(out_file_path)?;
+        for class in summary.referenced_classes {
+            let class = Some(OsStr::new(&class));
 
-                let mut compiler = Compiler::new(&mut tokenizer, &mut out_file);
-                compiler.compile()?;
+            if paths.iter().any(|path| path.file_stem() == class) {
+                continue;
+            }
+
+            if let Some(path) = library.iter().find(|path| path.file_stem() == class) {
+                sources.push(fs::read_to_string(path)?);
+                paths.push(path.clone());
             }
         }
+
+        index += 1;
     }
 
-    return Ok(());
+    Ok(())
+}
+
+// Result of compiling a program.
+pub struct Compilation {
+    // The output for each source, in the same order as the sources.
+    pub outputs: Vec<Vec<u8>>,
+    // What the compiler found out about each class, in the same order.
+    pub classes: Vec<ClassInfo>,
+    // Classes with static initializers (--ext static-initializers), in the order their
+    // initializers must run at the start of the entry point: every class comes after the classes
+    // its initializers use.
+    pub bootstrap: Vec<String>,
+    // Class and name of the function the program starts with, Sys.init or Main.main, if the
+    // program has it.
+    pub entry_point: Option<(String, String)>,
+    // Warnings, such as those of --lint-precedence, in the order they were found.
+    pub warnings: Vec<String>,
+}
+
+// Facts about a class that aren't part of the parse tree but that a code generator needs.
+#[derive(Clone, Debug)]
+pub struct ClassInfo {
+    pub name: String,
+    pub subroutines: Vec<SubroutineInfo>,
+    // Static variables with an initializer, in the order they are declared, which is the order
+    // their initializers run in.
+    pub static_initializers: Vec<String>,
+    // The calls the static initializers make, as in SubroutineInfo::calls.
+    pub static_initializer_calls: Vec<CallInfo>,
+}
+
+#[derive(Clone, Debug)]
+pub struct SubroutineInfo {
+    pub name: String,
+    pub kind: SubroutineKind,
+    // The return type as written, such as "int" or "Ball", or None for void.
+    pub return_type: Option<String>,
+    // Number of local variable slots (the nLocals of the VM function). Every declaration gets
+    // its own slot, including those in blocks (--ext block-variables).
+    pub local_count: usize,
+    // The calls the subroutine makes, in the order they are made: the calls in the arguments of
+    // a call come before it.
+    pub calls: Vec<CallInfo>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct CallInfo {
+    // Class of the called subroutine, or None when it can't be known, as for a call on the
+    // result of a subroutine the program doesn't define.
+    pub class: Option<String>,
+    pub subroutine: String,
+    // Index, in the same calls, of the call whose result this call is made on (--ext
+    // call-chains).
+    pub on_result_of: Option<usize>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SubroutineKind {
+    Constructor,
+    Function,
+    Method,
+}
+
+// Compiles the classes of a program.
+pub fn compile_program(sources: &[String], options: &Options) -> Result<Compilation> {
+    options.check()?;
+
+    let mut compilation = Compilation {
+        outputs: Vec::new(),
+        classes: Vec::new(),
+        bootstrap: Vec::new(),
+        entry_point: None,
+        warnings: Vec::new(),
+    };
+
+    let mut summaries = Vec::new();
+
+    for source in sources {
+        let mut output = Vec::new();
+        let mut summary = compile_class(&mut source.as_bytes(), &mut output, options)?;
+
+        compilation.outputs.push(output);
+        compilation.warnings.append(&mut summary.warnings);
+
+        summaries.push(summary);
+    }
+
+    compilation.classes = summaries.into_iter().map(|summary| summary.info).collect();
+    resolve_chained_calls(&mut compilation.classes)?;
+    compilation.bootstrap = bootstrap_order(&compilation.classes)?;
+    compilation.entry_point = entry_point(&compilation.classes);
+
+    if !compilation.bootstrap.is_empty() {
+        check_entry_point(&compilation)?;
+    }
+
+    Ok(compilation)
+}
+
+// Sets the class of every call made on the result of another call, which is the return type
+// of that call, now that the subroutines of all classes are known.
+fn resolve_chained_calls(classes: &mut [ClassInfo]) -> Result<()> {
+    let mut return_types = Vec::new();
+
+    for class in classes.iter() {
+        for subroutine in &class.subroutines {
+            return_types.push((
+                class.name.clone(),
+                subroutine.name.clone(),
+                subroutine.return_type.clone(),
+            ));
+        }
+    }
+
+    for class in classes.iter_mut() {
+        for subroutine in &mut class.subroutines {
+            let caller = format!("{}.{}", class.name, subroutine.name);
+            resolve_calls(&mut subroutine.calls, &caller, &return_types)?;
+        }
+
+        resolve_calls(
+            &mut class.static_initializer_calls,
+            &class.name,
+            &return_types,
+        )?;
+    }
+
+    Ok(())
+}
+
+// Sets the class of the chained calls among `calls`, which `caller` makes. `return_types` lists
+// the class, name and return type of every subroutine of the program.
+fn resolve_calls(
+    calls: &mut [CallInfo],
+    caller: &str,
+    return_types: &[(String, String, Option<String>)],
+) -> Result<()> {
+    for index in 0..calls.len() {
+        let previous = match calls[index].on_result_of {
+            Some(previous) => &calls[previous],
+            None => continue,
+        };
+
+        let return_type = match &previous.class {
+            Some(previous_class) => return_types
+                .iter()
+                .find(|(class_name, subroutine_name, _)| {
+                    class_name == previous_class && *subroutine_name == previous.subroutine
+                })
+                .map(|(_, _, return_type)| return_type),
+            None => None,
+        };
+
+        calls[index].class = match return_type {
+            // Not a subroutine of the program, such as one of the Jack OS.
+            None => None,
+            Some(Some(return_type))
+                if !matches!(return_type.as_str(), "int" | "char" | "boolean") =>
+            {
+                Some(return_type.clone())
+            }
+            Some(return_type) => bail!(
+                "{} calls {} on the result of {}.{}, but that returns {}, which has no methods.",
+                caller,
+                calls[index].subroutine,
+                previous.class.as_deref().unwrap_or(""),
+                previous.subroutine,
+                return_type.as_deref().unwrap_or("void")
+            ),
+        };
+    }
+
+    Ok(())
+}
+
+// The function the program starts with: Sys.init when there's a Sys class, as in the Jack OS,
+// and Main.main otherwise. None if the program doesn't have that subroutine.
+fn entry_point(classes: &[ClassInfo]) -> Option<(String, String)> {
+    let (class_name, subroutine_name) = if classes.iter().any(|class| class.name == "Sys") {
+        ("Sys", "init")
+    } else {
+        ("Main", "main")
+    };
+
+    classes
+        .iter()
+        .find(|class| class.name == class_name)?
+        .subroutines
+        .iter()
+        .find(|subroutine| subroutine.name == subroutine_name)
+        .map(|_| (class_name.to_string(), subroutine_name.to_string()))
+}
+
+// The static initializers run at the start of the entry point, which must be a function: a
+// method or constructor would already need an object of its class.
+fn check_entry_point(compilation: &Compilation) -> Result<()> {
+    let (class_name, subroutine_name) = match &compilation.entry_point {
+        Some(entry_point) => entry_point,
+        None => bail!(
+            "The static initializers of {} run at the start of Sys.init or Main.main, but the program has neither function.",
+            compilation.bootstrap.join(", ")
+        ),
+    };
+
+    let kind = compilation
+        .classes
+        .iter()
+        .filter(|class| &class.name == class_name)
+        .flat_map(|class| &class.subroutines)
+        .find(|subroutine| &subroutine.name == subroutine_name)
+        .map(|subroutine| subroutine.kind);
+
+    if kind != Some(SubroutineKind::Function) {
+        bail!(
+            "{}.{} runs the static initializers of {}, so it must be a function.",
+            class_name,
+            subroutine_name,
+            compilation.bootstrap.join(", ")
+        );
+    }
+
+    Ok(())
+}
+
+struct ClassSummary {
+    info: ClassInfo,
+    referenced_classes: Vec<String>,
+    warnings: Vec<String>,
+}
+
+fn compile_class(
+    input: &mut dyn BufRead,
+    output: &mut dyn Write,
+    options: &Options,
+) -> Result<ClassSummary> {
+    let mut tokenizer = Tokenizer::new(input, options).peekable();
+
+    let mut compiler = Compiler::new(&mut tokenizer, output, options);
+    compiler.compile()?;
+
+    Ok(ClassSummary {
+        info: ClassInfo {
+            name: compiler.class_name().to_string(),
+            subroutines: compiler.subroutines().to_vec(),
+            static_initializers: compiler.static_initializers().to_vec(),
+            static_initializer_calls: compiler.static_initializer_calls().to_vec(),
+        },
+        referenced_classes: compiler.referenced_classes(),
+        warnings: compiler.warnings().to_vec(),
+    })
+}
+
+// Classes with static initializers, ordered so that every class comes after the classes whose
+// initializers its own initializers rely on.
+fn bootstrap_order(classes: &[ClassInfo]) -> Result<Vec<String>> {
+    let mut order = Vec::new();
+    let mut visiting = Vec::new();
+
+    for class in classes {
+        visit_initializers(class, classes, &mut visiting, &mut order)?;
+    }
+
+    Ok(order)
+}
+
+fn visit_initializers(
+    class: &ClassInfo,
+    classes: &[ClassInfo],
+    visiting: &mut Vec<String>,
+    order: &mut Vec<String>,
+) -> Result<()> {
+    if class.static_initializers.is_empty() || order.contains(&class.name) {
+        return Ok(());
+    }
+
+    if let Some(position) = visiting.iter().position(|name| *name == class.name) {
+        bail!(
+            "The static initializers of {} depend on each other.",
+            visiting[position..].join(", ")
+        );
+    }
+
+    visiting.push(class.name.clone());
+
+    for dependency in initializer_dependencies(class, classes) {
+        if dependency.name != class.name {
+            visit_initializers(dependency, classes, visiting, order)?;
+        }
+    }
+
+    visiting.pop();
+    order.push(class.name.clone());
+
+    Ok(())
+}
+
+// The classes with static initializers that the initializers of `class` call into: directly, or
+// through the subroutines they call, whether or not the classes along the way have initializers.
+fn initializer_dependencies<'a>(
+    class: &'a ClassInfo,
+    classes: &'a [ClassInfo],
+) -> Vec<&'a ClassInfo> {
+    let mut dependencies: Vec<&ClassInfo> = Vec::new();
+    let mut visited: Vec<(&str, &str)> = Vec::new();
+    let mut calls: Vec<&CallInfo> = class.static_initializer_calls.iter().rev().collect();
+
+    while let Some(call) = calls.pop() {
+        // Calls whose class is unknown, or isn't part of the program like those of the Jack OS,
+        // can't reach a static initializer.
+        let callee = match call
+            .class
+            .as_ref()
+            .and_then(|name| classes.iter().find(|class| &class.name == name))
+        {
+            Some(callee) => callee,
+            None => continue,
+        };
+
+        if !callee.static_initializers.is_empty()
+            && !dependencies.iter().any(|class| class.name == callee.name)
+        {
+            dependencies.push(callee);
+        }
+
+        if visited.contains(&(callee.name.as_str(), call.subroutine.as_str())) {
+            continue;
+        }
+
+        visited.push((&callee.name, &call.subroutine));
+
+        if let Some(subroutine) = callee
+            .subroutines
+            .iter()
+            .find(|subroutine| subroutine.name == call.subroutine)
+        {
+            calls.extend(subroutine.calls.iter().rev());
+        }
+    }
+
+    dependencies
 }
@@ -1,23 +1,214 @@
-use anyhow::{bail, Result};
-use std::{fmt::Debug, fs::File, io::BufReader, iter::Peekable};
+use crate::{Extension, Options};
+use anyhow::{anyhow, bail, Result};
+use std::{fmt::Debug, io::BufRead, iter::Peekable};
 use utf8_chars::{BufReadCharsExt, Chars};
 
 pub struct Tokenizer<'a> {
-    file: Peekable<Chars<'a, BufReader<File>>>,
+    file: Peekable<Chars<'a, dyn BufRead + 'a>>,
+    options: &'a Options,
+    after_minus: bool,
 }
 
 impl<'a> Tokenizer<'a> {
-    pub fn new(file: &mut BufReader<File>) -> Tokenizer {
+    pub fn new(file: &'a mut (dyn BufRead + 'a), options: &'a Options) -> Tokenizer<'a> {
         Tokenizer {
             file: file.chars().peekable(),
+            options,
+            after_minus: false,
         }
     }
+
+    // Reads the digits of a hexadecimal (0x) or binary (0b) literal whose prefix has just been
+    // consumed.
+    fn read_radix_literal(&mut self, prefix: &str, radix: u32) -> Result<Token> {
+        let mut digits = String::new();
+
+        while let Some(Ok(value)) = self.file.peek() {
+            if !value.is_ascii_alphanumeric() {
+                break;
+            }
+
+            digits.push(*value);
+            self.file.next();
+        }
+
+        let source = format!("{}{}", prefix, digits);
+
+        if digits.is_empty() || !digits.chars().all(|digit| digit.is_digit(radix)) {
+            bail!("invalid integer constant {}", source);
+        }
+
+        let value = u32::from_str_radix(&digits, radix).unwrap_or(u32::MAX);
+        self.integer_constant(value, &source)
+    }
+
+    // Reads a character literal whose opening quote has just been consumed. The literal stands
+    // for the character's code in the Hack character set.
+    fn read_character_literal(&mut self) -> Result<Token> {
+        let value = match self.file.next() {
+            Some(Ok('\\')) => match self.read_escape()?.as_slice() {
+                [code] => *code,
+                _ => bail!("\\t stands for four spaces, so it can't be a character literal"),
+            },
+            Some(Ok('\'')) => bail!("empty character literal"),
+            Some(Ok(value)) => hack_character_code(value)?,
+            _ => bail!("unterminated character literal"),
+        };
+
+        match self.file.next() {
+            Some(Ok('\'')) => self.integer_constant(value.into(), "character literal"),
+            _ => bail!("a character literal must contain exactly one character"),
+        }
+    }
+
+    // `source` is the literal as written, which is what the error message shows: `value` saturates
+    // for literals that don't even fit in a u32.
+    fn integer_constant(&self, value: u32, source: &str) -> Result<Token> {
+        // -32768 is tokenized as a minus followed by 32768, so 32768 is let through after a minus.
+        // The compiler makes sure that minus is unary, and that --ext literals is enabled.
+        if value == IntegerConstantValue::NEGATIVE_LIMIT.into() && self.after_minus {
+            return Ok(Token::IntegerConstant(
+                IntegerConstantValue::negative_limit(),
+            ));
+        }
+
+        match IntegerConstantValue::new(value) {
+            Ok(value) => Ok(Token::IntegerConstant(value)),
+            Err(_) => Err(IntegerConstantValue::out_of_range(source)),
+        }
+    }
+
+    // Reads the escape sequence following a backslash and returns the Hack character codes it
+    // stands for. \t has no Hack character code and expands to four spaces; every other escape is
+    // a single character.
+    fn read_escape(&mut self) -> Result<Vec<u16>> {
+        match self.file.next() {
+            Some(Ok('n')) => Ok(vec![NEWLINE]),
+            Some(Ok('t')) => Ok(vec![' ' as u16; 4]),
+            Some(Ok('\\')) => Ok(vec!['\\' as u16]),
+            Some(Ok('\'')) => Ok(vec!['\'' as u16]),
+            Some(Ok('"')) => Ok(vec!['"' as u16]),
+            Some(Ok('x')) => Ok(vec![self.read_numeric_escape()?]),
+            Some(Ok(value)) => bail!("unknown escape sequence \\{}", value),
+            _ => bail!("unterminated escape sequence"),
+        }
+    }
+
+    // Reads the two hexadecimal digits of a \x escape, which must name a Hack character code.
+    fn read_numeric_escape(&mut self) -> Result<u16> {
+        let mut digits = String::new();
+
+        for _ in 0..2 {
+            match self.file.next() {
+                Some(Ok(value)) if value.is_ascii_hexdigit() => digits.push(value),
+                _ => bail!("a \\x escape must be followed by two hexadecimal digits"),
+            }
+        }
+
+        let value = u16::from_str_radix(&digits, 16)?;
+
+        match value {
+            32..=126 | NEWLINE..=LAST_HACK_CHARACTER => Ok(value),
+            _ => bail!(
+                "\\x{} is not a character code of the Hack character set",
+                digits
+            ),
+        }
+    }
+
+    // Reads a string constant whose opening quote has just been consumed, decoding escape
+    // sequences.
+    fn read_escaped_string_constant(&mut self) -> Result<Token> {
+        let mut value = String::new();
+
+        loop {
+            match self.file.next() {
+                Some(Ok('"')) => return Ok(Token::StringConstant(value)),
+                Some(Ok('\\')) => {
+                    for code in self.read_escape()? {
+                        value.push(char::from_u32(code.into()).unwrap());
+                    }
+                }
+                Some(Ok('\n')) | None => bail!("unterminated string constant"),
+                Some(Ok(character)) => value.push(character),
+                Some(Err(error)) => return Err(error.into()),
+            }
+        }
+    }
+}
+
+// Keywords introduced by language extensions, with the extension that introduces them.
+pub fn keyword_extension(word: &str) -> Option<(Keyword, Extension)> {
+    match word {
+        "for" => Some((Keyword::For, Extension::Loops)),
+        "break" => Some((Keyword::Break, Extension::Loops)),
+        "continue" => Some((Keyword::Continue, Extension::Loops)),
+        "switch" => Some((Keyword::Switch, Extension::Switch)),
+        "case" => Some((Keyword::Case, Extension::Switch)),
+        "default" => Some((Keyword::Default, Extension::Switch)),
+        "const" => Some((Keyword::Const, Extension::Constants)),
+        "enum" => Some((Keyword::Enum, Extension::Constants)),
+        _ => None,
+    }
+}
+
+// Extension keywords are recognized only when their extension is enabled and are ordinary
+// identifiers otherwise.
+fn extension_keyword(options: &Options, word: &str) -> Option<Keyword> {
+    keyword_extension(word)
+        .filter(|(_, extension)| options.has_extension(*extension))
+        .map(|(keyword, _)| keyword)
+}
+
+// Reads the '=' of `+=`, `-=`, `&=` or `|=`, or the second symbol of `++` or `--`, when it
+// directly follows `symbol`, and returns the compound symbol (--ext compound-assignment). `a--b`
+// then reads as `a --` followed by `b`, as in C.
+fn read_compound_symbol(
+    chars: &mut Peekable<Chars<'_, dyn BufRead + '_>>,
+    symbol: Symbol,
+) -> Option<Symbol> {
+    let compound = match (symbol, chars.peek()) {
+        (Symbol::Plus, Some(Ok('='))) => Symbol::PlusEqual,
+        (Symbol::Minus, Some(Ok('='))) => Symbol::MinusEqual,
+        (Symbol::And, Some(Ok('='))) => Symbol::AndEqual,
+        (Symbol::Or, Some(Ok('='))) => Symbol::OrEqual,
+        (Symbol::Plus, Some(Ok('+'))) => Symbol::Increment,
+        (Symbol::Minus, Some(Ok('-'))) => Symbol::Decrement,
+        _ => return None,
+    };
+
+    chars.next();
+    Some(compound)
+}
+
+// Hack character set code for a newline.
+const NEWLINE: u16 = 128;
+
+// Hack character set code for F12, the last key code.
+const LAST_HACK_CHARACTER: u16 = 152;
+
+fn hack_character_code(value: char) -> Result<u16> {
+    match value {
+        ' '..='~' => Ok(value as u16),
+        _ => bail!(
+            "character {:?} is not part of the Hack character set",
+            value
+        ),
+    }
 }
 
 impl<'a> Iterator for Tokenizer<'a> {
-    type Item = Token;
+    type Item = Result<Token>;
 
     fn next(&mut self) -> Option<Self::Item> {
+        let token = self.read_token();
+        self.after_minus = matches!(token, Some(Ok(Token::Symbol(Symbol::Minus))));
+        token
+    }
+}
+
+impl<'a> Tokenizer<'a> {
+    fn read_token(&mut self) -> Option<Result<Token>> {
         let mut buffer = String::new();
         let chars = &mut self.file;
 
@@ -53,7 +244,7 @@ impl<'a> Iterator for Tokenizer<'a> {
                         buffer.clear();
                     } else {
                         buffer.clear();
-                        return Some(Token::Symbol(Symbol::Divide));
+                        return Some(Ok(Token::Symbol(Symbol::Divide)));
                     }
                 }
                 " " => {
@@ -70,123 +261,155 @@ impl<'a> Iterator for Tokenizer<'a> {
                 }
                 "(" => {
                     buffer.clear();
-                    return Some(Token::Symbol(Symbol::LeftRoundBracket));
+                    return Some(Ok(Token::Symbol(Symbol::LeftRoundBracket)));
                 }
                 ")" => {
                     buffer.clear();
-                    return Some(Token::Symbol(Symbol::RightRoundBracket));
+                    return Some(Ok(Token::Symbol(Symbol::RightRoundBracket)));
                 }
                 "[" => {
                     buffer.clear();
-                    return Some(Token::Symbol(Symbol::LeftSquareBracket));
+                    return Some(Ok(Token::Symbol(Symbol::LeftSquareBracket)));
                 }
                 "]" => {
                     buffer.clear();
-                    return Some(Token::Symbol(Symbol::RightSquareBracket));
+                    return Some(Ok(Token::Symbol(Symbol::RightSquareBracket)));
                 }
                 "{" => {
                     buffer.clear();
-                    return Some(Token::Symbol(Symbol::LeftCurlyBracket));
+                    return Some(Ok(Token::Symbol(Symbol::LeftCurlyBracket)));
                 }
                 "}" => {
                     buffer.clear();
-                    return Some(Token::Symbol(Symbol::RightCurlyBracket));
+                    return Some(Ok(Token::Symbol(Symbol::RightCurlyBracket)));
                 }
                 "," => {
                     buffer.clear();
-                    return Some(Token::Symbol(Symbol::Comma));
+                    return Some(Ok(Token::Symbol(Symbol::Comma)));
                 }
                 ";" => {
                     buffer.clear();
-                    return Some(Token::Symbol(Symbol::Semicolon));
+                    return Some(Ok(Token::Symbol(Symbol::Semicolon)));
                 }
                 "=" => {
                     buffer.clear();
-                    return Some(Token::Symbol(Symbol::Equal));
+                    return Some(Ok(Token::Symbol(Symbol::Equal)));
                 }
                 "." => {
                     buffer.clear();
-                    return Some(Token::Symbol(Symbol::Dot));
+                    return Some(Ok(Token::Symbol(Symbol::Dot)));
                 }
                 "+" => {
                     buffer.clear();
-                    return Some(Token::Symbol(Symbol::Plus));
+
+                    if self.options.has_extension(Extension::CompoundAssignment) {
+                        if let Some(symbol) = read_compound_symbol(chars, Symbol::Plus) {
+                            return Some(Ok(Token::Symbol(symbol)));
+                        }
+                    }
+
+                    return Some(Ok(Token::Symbol(Symbol::Plus)));
                 }
                 "-" => {
                     buffer.clear();
-                    return Some(Token::Symbol(Symbol::Minus));
+
+                    if self.options.has_extension(Extension::CompoundAssignment) {
+                        if let Some(symbol) = read_compound_symbol(chars, Symbol::Minus) {
+                            return Some(Ok(Token::Symbol(symbol)));
+                        }
+                    }
+
+                    return Some(Ok(Token::Symbol(Symbol::Minus)));
                 }
                 "*" => {
                     buffer.clear();
-                    return Some(Token::Symbol(Symbol::Times));
+                    return Some(Ok(Token::Symbol(Symbol::Times)));
                 }
                 "&" => {
                     buffer.clear();
-                    return Some(Token::Symbol(Symbol::And));
+
+                    if self.options.has_extension(Extension::CompoundAssignment) {
+                        if let Some(symbol) = read_compound_symbol(chars, Symbol::And) {
+                            return Some(Ok(Token::Symbol(symbol)));
+                        }
+                    }
+
+                    return Some(Ok(Token::Symbol(Symbol::And)));
                 }
                 "|" => {
                     buffer.clear();
-                    return Some(Token::Symbol(Symbol::Or));
+
+                    if self.options.has_extension(Extension::CompoundAssignment) {
+                        if let Some(symbol) = read_compound_symbol(chars, Symbol::Or) {
+                            return Some(Ok(Token::Symbol(symbol)));
+                        }
+                    }
+
+                    return Some(Ok(Token::Symbol(Symbol::Or)));
                 }
                 "~" => {
                     buffer.clear();
-                    return Some(Token::Symbol(Symbol::Not));
+                    return Some(Ok(Token::Symbol(Symbol::Not)));
+                }
+                ":" => {
+                    buffer.clear();
+                    return Some(Ok(Token::Symbol(Symbol::Colon)));
                 }
                 "<" => {
                     buffer.clear();
-                    return Some(Token::Symbol(Symbol::SmallerThan));
+                    return Some(Ok(Token::Symbol(Symbol::SmallerThan)));
                 }
                 ">" => {
                     buffer.clear();
-                    return Some(Token::Symbol(Symbol::GreaterThan));
+                    return Some(Ok(Token::Symbol(Symbol::GreaterThan)));
                 }
                 "class" => {
                     buffer.clear();
-                    return Some(Token::Keyword(Keyword::Class));
+                    return Some(Ok(Token::Keyword(Keyword::Class)));
                 }
                 "constructor" => {
                     buffer.clear();
-                    return Some(Token::Keyword(Keyword::Constructor));
+                    return Some(Ok(Token::Keyword(Keyword::Constructor)));
                 }
                 "method" => {
                     buffer.clear();
-                    return Some(Token::Keyword(Keyword::Method));
+                    return Some(Ok(Token::Keyword(Keyword::Method)));
                 }
                 "function" => {
                     buffer.clear();
-                    return Some(Token::Keyword(Keyword::Function));
+                    return Some(Ok(Token::Keyword(Keyword::Function)));
                 }
                 "int" => {
                     buffer.clear();
-                    return Some(Token::Keyword(Keyword::Int));
+                    return Some(Ok(Token::Keyword(Keyword::Int)));
                 }
                 "boolean" => {
                     buffer.clear();
-                    return Some(Token::Keyword(Keyword::Boolean));
+                    return Some(Ok(Token::Keyword(Keyword::Boolean)));
                 }
                 "char" => {
                     buffer.clear();
-                    return Some(Token::Keyword(Keyword::Char));
+                    return Some(Ok(Token::Keyword(Keyword::Char)));
                 }
                 "void" => {
                     buffer.clear();
-                    return Some(Token::Keyword(Keyword::Void));
+                    return Some(Ok(Token::Keyword(Keyword::Void)));
                 }
                 "var" => {
                     buffer.clear();
-                    return Some(Token::Keyword(Keyword::Var));
+                    return Some(Ok(Token::Keyword(Keyword::Var)));
                 }
                 "static" => {
                     buffer.clear();
-                    return Some(Token::Keyword(Keyword::Static));
+                    return Some(Ok(Token::Keyword(Keyword::Static)));
                 }
                 "field" => {
                     buffer.clear();
-                    return Some(Token::Keyword(Keyword::Field));
+                    return Some(Ok(Token::Keyword(Keyword::Field)));
                 }
                 "let" => {
                     buffer.clear();
-                    return Some(Token::Keyword(Keyword::Let));
+                    return Some(Ok(Token::Keyword(Keyword::Let)));
                 }
                 "do" => {
                     let peek = match chars.peek() {
@@ -196,50 +419,70 @@ impl<'a> Iterator for Tokenizer<'a> {
 
                     if peek == &' ' {
                         buffer.clear();
-                        return Some(Token::Keyword(Keyword::Do));
+                        return Some(Ok(Token::Keyword(Keyword::Do)));
                     }
                 }
                 "if" => {
                     buffer.clear();
-                    return Some(Token::Keyword(Keyword::If));
+                    return Some(Ok(Token::Keyword(Keyword::If)));
                 }
                 "else" => {
                     buffer.clear();
-                    return Some(Token::Keyword(Keyword::Else));
+                    return Some(Ok(Token::Keyword(Keyword::Else)));
                 }
                 "while" => {
                     buffer.clear();
-                    return Some(Token::Keyword(Keyword::While));
+                    return Some(Ok(Token::Keyword(Keyword::While)));
                 }
                 "return" => {
                     buffer.clear();
-                    return Some(Token::Keyword(Keyword::Return));
+                    return Some(Ok(Token::Keyword(Keyword::Return)));
                 }
                 "true" => {
                     buffer.clear();
-                    return Some(Token::Keyword(Keyword::True));
+                    return Some(Ok(Token::Keyword(Keyword::True)));
                 }
                 "false" => {
                     buffer.clear();
-                    return Some(Token::Keyword(Keyword::False));
+                    return Some(Ok(Token::Keyword(Keyword::False)));
                 }
                 "null" => {
                     buffer.clear();
-                    return Some(Token::Keyword(Keyword::Null));
+                    return Some(Ok(Token::Keyword(Keyword::Null)));
                 }
                 "this" => {
                     buffer.clear();
-                    return Some(Token::Keyword(Keyword::This));
+                    return Some(Ok(Token::Keyword(Keyword::This)));
                 }
                 "\"" => {
                     buffer.clear();
 
-                    while !buffer.ends_with("\"") {
-                        buffer.push(chars.next().unwrap().unwrap());
+                    if self.options.has_extension(Extension::StringEscapes) {
+                        return Some(self.read_escaped_string_constant());
                     }
 
-                    let buffer = buffer.trim_end_matches("\"");
-                    return Some(Token::StringConstant(buffer.to_string()));
+                    loop {
+                        match chars.next() {
+                            Some(Ok('"')) => break,
+                            Some(Ok('\n')) | None => {
+                                return Some(Err(anyhow!("unterminated string constant")));
+                            }
+                            Some(Ok(character)) => buffer.push(character),
+                            Some(Err(error)) => return Some(Err(error.into())),
+                        }
+                    }
+
+                    return Some(Ok(Token::StringConstant(buffer)));
+                }
+                "'" => {
+                    if let Err(error) = self
+                        .options
+                        .require_extension(Extension::Literals, "character literal")
+                    {
+                        return Some(Err(error));
+                    }
+
+                    return Some(self.read_character_literal());
                 }
                 _ => {
                     let peek = match chars.peek() {
@@ -251,16 +494,38 @@ impl<'a> Iterator for Tokenizer<'a> {
                         && !peek.is_ascii_alphabetic()
                         && !peek.is_ascii_digit()
                     {
+                        if let Some(keyword) = extension_keyword(self.options, &buffer) {
+                            buffer.clear();
+                            return Some(Ok(Token::Keyword(keyword)));
+                        }
+
                         let identifier_value = IdentifierValue::new(buffer.clone()).unwrap();
                         buffer.clear();
-                        return Some(Token::Identifier(identifier_value));
+                        return Some(Ok(Token::Identifier(identifier_value)));
+                    }
+
+                    if buffer == "0" && matches!(peek, 'x' | 'X' | 'b' | 'B') {
+                        if let Err(error) = self.options.require_extension(
+                            Extension::Literals,
+                            "hexadecimal or binary integer constant",
+                        ) {
+                            return Some(Err(error));
+                        }
+
+                        let prefix = format!("0{}", chars.next().unwrap().unwrap());
+                        let radix = if prefix == "0x" || prefix == "0X" {
+                            16
+                        } else {
+                            2
+                        };
+                        return Some(self.read_radix_literal(&prefix, radix));
                     }
 
                     if buffer.chars().next().unwrap().is_ascii_digit() && !peek.is_ascii_digit() {
-                        let integer_constant_value =
-                            IntegerConstantValue::new(buffer.parse().unwrap()).unwrap();
+                        let value = buffer.parse().unwrap_or(u32::MAX);
+                        let token = self.integer_constant(value, &buffer);
                         buffer.clear();
-                        return Some(Token::IntegerConstant(integer_constant_value));
+                        return Some(token);
                     }
                 }
             }
@@ -322,6 +587,14 @@ pub enum Keyword {
     Else,
     While,
     Return,
+    For,
+    Break,
+    Continue,
+    Switch,
+    Case,
+    Default,
+    Const,
+    Enum,
 }
 
 impl Debug for Keyword {
@@ -348,6 +621,14 @@ impl Debug for Keyword {
             Keyword::Else => write!(f, "else"),
             Keyword::While => write!(f, "while"),
             Keyword::Return => write!(f, "return"),
+            Keyword::For => write!(f, "for"),
+            Keyword::Break => write!(f, "break"),
+            Keyword::Continue => write!(f, "continue"),
+            Keyword::Switch => write!(f, "switch"),
+            Keyword::Case => write!(f, "case"),
+            Keyword::Default => write!(f, "default"),
+            Keyword::Const => write!(f, "const"),
+            Keyword::Enum => write!(f, "enum"),
         }
     }
 }
@@ -373,6 +654,14 @@ pub enum Symbol {
     GreaterThan,
     Equal,
     Not,
+    Colon,
+    // Compound assignment symbols (--ext compound-assignment).
+    PlusEqual,
+    MinusEqual,
+    AndEqual,
+    OrEqual,
+    Increment,
+    Decrement,
 }
 
 impl Debug for Symbol {
@@ -397,6 +686,47 @@ impl Debug for Symbol {
             Symbol::GreaterThan => write!(f, "&gt;"),
             Symbol::Equal => write!(f, "="),
             Symbol::Not => write!(f, "~"),
+            Symbol::Colon => write!(f, ":"),
+            Symbol::PlusEqual => write!(f, "+="),
+            Symbol::MinusEqual => write!(f, "-="),
+            Symbol::AndEqual => write!(f, "&amp;="),
+            Symbol::OrEqual => write!(f, "|="),
+            Symbol::Increment => write!(f, "++"),
+            Symbol::Decrement => write!(f, "--"),
+        }
+    }
+}
+
+impl Symbol {
+    // The symbol as written in Jack source, for diagnostics.
+    pub fn text(&self) -> &'static str {
+        match self {
+            Symbol::LeftCurlyBracket => "{",
+            Symbol::RightCurlyBracket => "}",
+            Symbol::LeftRoundBracket => "(",
+            Symbol::RightRoundBracket => ")",
+            Symbol::LeftSquareBracket => "[",
+            Symbol::RightSquareBracket => "]",
+            Symbol::Dot => ".",
+            Symbol::Comma => ",",
+            Symbol::Semicolon => ";",
+            Symbol::Plus => "+",
+            Symbol::Minus => "-",
+            Symbol::Times => "*",
+            Symbol::Divide => "/",
+            Symbol::And => "&",
+            Symbol::Or => "|",
+            Symbol::SmallerThan => "<",
+            Symbol::GreaterThan => ">",
+            Symbol::Equal => "=",
+            Symbol::Not => "~",
+            Symbol::Colon => ":",
+            Symbol::PlusEqual => "+=",
+            Symbol::MinusEqual => "-=",
+            Symbol::AndEqual => "&=",
+            Symbol::OrEqual => "|=",
+            Symbol::Increment => "++",
+            Symbol::Decrement => "--",
         }
     }
 }
@@ -407,13 +737,34 @@ pub struct IntegerConstantValue {
 }
 
 impl IntegerConstantValue {
-    pub fn new(value: u16) -> Result<IntegerConstantValue> {
+    // Magnitude of -32768, the smallest Hack integer. 32768 itself doesn't fit in 16-bit two's
+    // complement, so it is only valid as the operand of a unary minus.
+    pub const NEGATIVE_LIMIT: u16 = 32768;
+
+    pub fn negative_limit() -> IntegerConstantValue {
+        IntegerConstantValue {
+            value: Self::NEGATIVE_LIMIT,
+        }
+    }
+
+    pub fn new(value: u32) -> Result<IntegerConstantValue> {
         if value <= 32767 {
-            return Ok(IntegerConstantValue { value });
+            return Ok(IntegerConstantValue {
+                value: value as u16,
+            });
         } else {
-            bail!("an IntegerConstant must have a value in the range 0...32767")
+            Err(Self::out_of_range(&value.to_string()))
         }
     }
+
+    // The diagnostic for an integer constant outside 0...32767, wherever it is found. `source` is
+    // the constant as written.
+    pub fn out_of_range(source: &str) -> anyhow::Error {
+        anyhow!(
+            "integer constant {} is out of range: an IntegerConstant must have a value in the range 0...32767",
+            source
+        )
+    }
 }
 
 #[derive(Debug, PartialEq)]
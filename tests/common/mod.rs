@@ -0,0 +1,31 @@
+// Helpers shared by the integration tests. Each test crate uses only some of them.
+#![allow(dead_code)]
+
+use clap::Parser;
+use jackc::{Compilation, Options};
+
+#[derive(Parser)]
+struct Flags {
+    #[command(flatten)]
+    options: Options,
+}
+
+// Compiles `sources` in memory as one program, with the options of a jackc command line such as
+// "--ext loops --precedence c".
+pub fn compile<S: AsRef<str>>(flags: &str, sources: &[S]) -> anyhow::Result<Compilation> {
+    let flags = Flags::try_parse_from(std::iter::once("jackc").chain(flags.split_whitespace()))?;
+    let sources: Vec<String> = sources
+        .iter()
+        .map(|source| source.as_ref().to_string())
+        .collect();
+
+    jackc::compile_program(&sources, &flags.options)
+}
+
+// Like compile, for programs that must compile.
+pub fn compile_ok<S: AsRef<str>>(flags: &str, sources: &[S]) -> Compilation {
+    match compile(flags, sources) {
+        Ok(compilation) => compilation,
+        Err(error) => panic!("unexpected error: {:#}", error),
+    }
+}
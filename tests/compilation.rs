@@ -0,0 +1,175 @@
+// What compile_program reports about a program besides the parse trees.
+
+mod common;
+
+use common::compile_ok;
+use jackc::SubroutineKind;
+
+#[test]
+fn subroutines_and_their_local_counts() {
+    let source = "class Game {
+        field int score;
+
+        constructor Game new() { return this; }
+
+        method int score() { var int a, b; var Array c; return a; }
+
+        function void run(int n) {
+            var int i;
+
+            while (i < n) {
+                var int j;
+                let j = i;
+            }
+
+            if (i = 0) {
+                var boolean done, again;
+            }
+
+            return;
+        }
+    }";
+
+    let compilation = compile_ok("--ext block-variables", &[source]);
+    let class = &compilation.classes[0];
+
+    assert_eq!(class.name, "Game");
+
+    let subroutines: Vec<_> = class
+        .subroutines
+        .iter()
+        .map(|subroutine| {
+            (
+                subroutine.name.as_str(),
+                subroutine.kind,
+                subroutine.return_type.as_deref(),
+                subroutine.local_count,
+            )
+        })
+        .collect();
+
+    // Block variables get their own slots even when their blocks don't overlap.
+    assert_eq!(
+        subroutines,
+        vec![
+            ("new", SubroutineKind::Constructor, Some("Game"), 0),
+            ("score", SubroutineKind::Method, Some("int"), 3),
+            ("run", SubroutineKind::Function, None, 4),
+        ]
+    );
+}
+
+#[test]
+fn static_initializers_run_after_those_of_the_classes_they_use() {
+    let board = "class Board {
+        static int width = 16;
+        static int seed = Random.next(), height = 8;
+        function int cells() { return width * height; }
+    }";
+    let main = "class Main {
+        static int total = Board.cells();
+        function void main() { return; }
+    }";
+    let random = "class Random {
+        static int seed = 7;
+        function int next() { return seed; }
+    }";
+
+    let compilation = compile_ok("--ext static-initializers", &[main, board, random]);
+
+    assert_eq!(compilation.bootstrap, vec!["Random", "Board", "Main"]);
+    assert_eq!(
+        compilation.entry_point,
+        Some(("Main".to_string(), "main".to_string()))
+    );
+    assert_eq!(
+        compilation.classes[1].static_initializers,
+        vec!["width", "seed", "height"]
+    );
+}
+
+#[test]
+fn static_initializers_run_after_those_reached_through_other_subroutines() {
+    let c = "class C {
+        static int x = 5;
+        function int v() { return x; }
+    }";
+    let b = "class B {
+        function int get() { return C.v(); }
+    }";
+
+    // Through B.get, in a class without initializers.
+    let main = "class Main {
+        static int total = B.get();
+        function void main() { return; }
+    }";
+
+    let compilation = compile_ok("--ext static-initializers", &[main, b, c]);
+
+    assert_eq!(compilation.bootstrap, vec!["C", "Main"]);
+
+    // Through a function of the class itself.
+    let main = "class Main {
+        static int total = Main.helper();
+        function int helper() { return C.v(); }
+        function void main() { return; }
+    }";
+
+    let compilation = compile_ok("--ext static-initializers", &[main, c]);
+
+    assert_eq!(compilation.bootstrap, vec!["C", "Main"]);
+}
+
+#[test]
+fn the_entry_point_is_sys_init_when_there_is_a_sys_class() {
+    let main = "class Main { function void main() { return; } }";
+    let sys = "class Sys { function void init() { return; } }";
+
+    let compilation = compile_ok("", &[main, sys]);
+
+    assert!(compilation.bootstrap.is_empty());
+    assert_eq!(
+        compilation.entry_point,
+        Some(("Sys".to_string(), "init".to_string()))
+    );
+}
+
+#[test]
+fn calls_record_the_class_of_the_called_subroutine() {
+    let game = "class Game {
+        field Ball ball;
+        method Ball ball() { return ball; }
+        method void run() {
+            do this.ball().move(Math.max(1, 2));
+            do ball.bounce();
+            do draw();
+            return;
+        }
+        method void draw() { return; }
+    }";
+    let ball = "class Ball {
+        method Ball move(int steps) { return this; }
+        method void bounce() { return; }
+    }";
+
+    let compilation = compile_ok("--ext call-chains", &[game, ball]);
+
+    let call =
+        |class: Option<&str>, subroutine: &str, on_result_of: Option<usize>| jackc::CallInfo {
+            class: class.map(str::to_string),
+            subroutine: subroutine.to_string(),
+            on_result_of,
+        };
+
+    // Ball.move is declared in another class, after the call chained to Game.ball.
+    assert_eq!(
+        compilation.classes[0].subroutines[1].calls,
+        vec![
+            call(Some("Game"), "ball", None),
+            call(Some("Math"), "max", None),
+            call(Some("Ball"), "move", Some(0)),
+            call(Some("Ball"), "bounce", None),
+            call(Some("Game"), "draw", None),
+        ]
+    );
+}
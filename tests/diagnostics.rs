@@ -0,0 +1,639 @@
+// Errors and warnings reported by the compiler. Each test compiles a small program in memory with
+// the given command line options and checks the text of the diagnostic.
+
+mod common;
+
+use common::{compile, compile_ok};
+
+fn warnings(flags: &str, sources: &[&str]) -> Vec<String> {
+    compile_ok(flags, sources).warnings
+}
+
+fn error(flags: &str, sources: &[&str]) -> String {
+    match compile(flags, sources) {
+        Ok(_) => panic!("expected an error"),
+        Err(error) => format!("{:#}", error),
+    }
+}
+
+// A class Main with a function `main` holding `statements`.
+fn main_class(statements: &str) -> String {
+    format!(
+        "class Main {{ function void main() {{ var int a, b; {} return; }} }}",
+        statements
+    )
+}
+
+// Precedence lint
+
+const PRECEDENCE_WARNING: &str = "follows a lower precedence operator without parentheses; its meaning differs between --precedence=jack and --precedence=c";
+
+#[test]
+fn lint_precedence_warns_when_an_operator_binds_tighter_than_the_previous_one() {
+    let source = main_class("let a = 1 + 2 * 3;");
+
+    assert_eq!(
+        warnings("--lint-precedence", &[&source]),
+        vec![format!("Main.main: operator '*' {}", PRECEDENCE_WARNING)]
+    );
+}
+
+#[test]
+fn lint_precedence_is_silent_when_precedence_never_rises() {
+    let source = main_class("let a = 1 * 2 + 3 - 4 < 5 = 6 & 7 | 8; let b = (1 + 2) * 3;");
+
+    assert!(warnings("--lint-precedence", &[&source]).is_empty());
+    assert!(warnings("--lint-precedence --precedence c", &[&source]).is_empty());
+}
+
+#[test]
+fn lint_precedence_warns_once_per_rise_in_both_modes() {
+    let source = main_class("let a = 1 | 2 * 3 + 4 & 5; let b = 1 + 2 * 3 - 4 * 5;");
+
+    let expected = vec![
+        format!("Main.main: operator '*' {}", PRECEDENCE_WARNING),
+        format!("Main.main: operator '*' {}", PRECEDENCE_WARNING),
+        format!("Main.main: operator '*' {}", PRECEDENCE_WARNING),
+    ];
+
+    assert_eq!(warnings("--lint-precedence", &[&source]), expected);
+    assert_eq!(
+        warnings("--lint-precedence --precedence c", &[&source]),
+        expected
+    );
+}
+
+#[test]
+fn lint_precedence_is_off_by_default() {
+    let source = main_class("let a = 1 + 2 * 3;");
+
+    assert!(warnings("", &[&source]).is_empty());
+}
+
+// Extensions
+
+fn missing_extension(extension: &str, usage: &str) -> String {
+    format!(
+        "Feature {} ({}) is not part of standard Jack. Enable it with --ext {} or --edition extended.",
+        extension, usage, extension
+    )
+}
+
+#[test]
+fn literals_need_their_extension() {
+    assert_eq!(
+        error("", &[&main_class("let a = 0x1F;")]),
+        missing_extension(
+            "literals",
+            "hexadecimal or binary integer constant in Main.main"
+        )
+    );
+    assert_eq!(
+        error("", &[&main_class("let a = 0b101;")]),
+        missing_extension(
+            "literals",
+            "hexadecimal or binary integer constant in Main.main"
+        )
+    );
+    assert_eq!(
+        error("", &[&main_class("let a = 'x';")]),
+        missing_extension("literals", "character literal in Main.main")
+    );
+}
+
+#[test]
+fn string_escapes_are_plain_characters_without_their_extension() {
+    let source = main_class("do Output.printString(\"a\\n\");");
+
+    assert!(compile("", &[&source]).is_ok());
+    assert_eq!(
+        error("", &[&main_class("do Output.printString(\"a\\\"b\");")]),
+        "Expected to find symbol ). But found this instead: <identifier> b </identifier>"
+    );
+}
+
+#[test]
+fn loops_need_their_extension() {
+    assert_eq!(
+        error(
+            "",
+            &[&main_class("for (let a = 0; a < 3; let a = a + 1) {}")]
+        ),
+        missing_extension("loops", "'for' in Main.main")
+    );
+    assert_eq!(
+        error("", &[&main_class("while (a) { break; }")]),
+        missing_extension("loops", "'break' in Main.main")
+    );
+    assert_eq!(
+        error("", &[&main_class("while (a) { continue; }")]),
+        missing_extension("loops", "'continue' in Main.main")
+    );
+}
+
+#[test]
+fn else_if_needs_its_extension() {
+    assert_eq!(
+        error("", &[&main_class("if (a) {} else if (b) {}")]),
+        missing_extension("else-if", "'else if' in Main.main")
+    );
+}
+
+#[test]
+fn switch_needs_its_extension() {
+    assert_eq!(
+        error("", &[&main_class("switch (a) { case 1: let b = 1; }")]),
+        missing_extension("switch", "'switch' in Main.main")
+    );
+}
+
+#[test]
+fn constants_need_their_extension() {
+    assert_eq!(
+        error("", &[&class_with("const int A = 1;", "")]),
+        missing_extension("constants", "'const' in Main")
+    );
+    assert_eq!(
+        error("", &[&class_with("enum Color { RED, GREEN }", "")]),
+        missing_extension("constants", "'enum' in Main")
+    );
+}
+
+#[test]
+fn block_variables_need_their_extension() {
+    assert_eq!(
+        error("", &[&main_class("if (a) { var int c; }")]),
+        missing_extension(
+            "block-variables",
+            "'var' after the first statement or inside a block in Main.main"
+        )
+    );
+}
+
+#[test]
+fn array_initializers_need_their_extension() {
+    assert_eq!(
+        error("", &[&main_class("let a = [1, 2, 3];")]),
+        missing_extension("array-initializers", "array initializer in Main.main")
+    );
+}
+
+#[test]
+fn static_initializers_need_their_extension() {
+    assert_eq!(
+        error("", &[&class_with("static int count = 1;", "")]),
+        missing_extension(
+            "static-initializers",
+            "initializer of static variable count in Main"
+        )
+    );
+}
+
+#[test]
+fn call_chains_need_their_extension() {
+    let source = "class Main {
+        function Main make() { return null; }
+        method void draw() { do this.draw(); return; }
+        function void main() { do Main.make().draw(); return; }
+    }";
+
+    assert_eq!(
+        error("", &[source]),
+        missing_extension("call-chains", "call through this in Main.draw")
+    );
+    assert_eq!(
+        error("", &[&source.replace("do this.draw();", "do draw();")]),
+        missing_extension("call-chains", "call on the result of a call in Main.main")
+    );
+}
+
+#[test]
+fn multi_dimensional_arrays_need_their_extension() {
+    assert_eq!(
+        error("", &[&main_class("let a = b[1][2];")]),
+        missing_extension(
+            "multi-dimensional-arrays",
+            "chained array subscript in Main.main"
+        )
+    );
+}
+
+#[test]
+fn strict_edition_only_allows_standard_jack() {
+    let source = main_class("let a = 1;");
+
+    assert_eq!(
+        error("--edition strict --ext loops", &[&source]),
+        "--ext loops can't be combined with --edition strict, which only allows standard Jack."
+    );
+    assert_eq!(
+        error("--edition strict --precedence c", &[&source]),
+        "--precedence c can't be combined with --edition strict, which only allows standard Jack: it changes the meaning of standard programs."
+    );
+    assert!(compile("--edition strict", &[&source]).is_ok());
+    assert!(compile("--edition extended --precedence c", &[&source]).is_ok());
+}
+
+// Integer constants
+
+#[test]
+fn negative_limit_needs_literals() {
+    let source = main_class("let a = -32768;");
+
+    assert_eq!(
+        error("", &[&source]),
+        "Feature literals (-32768 in Main.main) is not part of standard Jack. Enable it with --ext literals or --edition extended."
+    );
+    assert!(compile("--ext literals", &[&source]).is_ok());
+}
+
+#[test]
+fn positive_limit_is_out_of_range() {
+    let expected = "integer constant 32768 is out of range: an IntegerConstant must have a value in the range 0...32767";
+
+    // The tokenizer lets 32768 through after any minus, so a binary minus is caught by the
+    // compiler, with the same diagnostic.
+    for statements in [
+        "let a = 32768;",
+        "let a = b - 32768;",
+        "switch (a) { case 32768: let b = 1; }",
+    ] {
+        assert_eq!(
+            error("--ext literals --ext switch", &[&main_class(statements)]),
+            format!("Main.main: {}", expected)
+        );
+    }
+    assert_eq!(
+        error(
+            "--ext literals --ext constants",
+            &[&class_with("const int A = 1 - 32768;", "")]
+        ),
+        format!("Main: {}", expected)
+    );
+}
+
+#[test]
+fn hexadecimal_and_binary_constants_must_be_in_range() {
+    assert_eq!(
+        error("--ext literals", &[&main_class("let a = 0x8000;")]),
+        "Main.main: integer constant 0x8000 is out of range: an IntegerConstant must have a value in the range 0...32767"
+    );
+    assert_eq!(
+        error(
+            "--ext literals",
+            &[&main_class("let a = 0b1000000000000000;")]
+        ),
+        "Main.main: integer constant 0b1000000000000000 is out of range: an IntegerConstant must have a value in the range 0...32767"
+    );
+    assert!(compile("--ext literals", &[&main_class("let a = 0x7FFF;")]).is_ok());
+}
+
+#[test]
+fn tokenizer_diagnostics_say_where_they_are() {
+    assert_eq!(
+        error("", &[&main_class("do Output.printString(\"abc);")]),
+        "Main.main: unterminated string constant"
+    );
+    assert_eq!(
+        error(
+            "--ext literals --ext constants",
+            &[&class_with("const int A = 0x8000;", "")]
+        ),
+        "Main: integer constant 0x8000 is out of range: an IntegerConstant must have a value in the range 0...32767"
+    );
+}
+
+// Switch
+
+#[test]
+fn case_labels_must_be_unique() {
+    assert_eq!(
+        error(
+            "--ext switch",
+            &[&main_class(
+                "switch (a) { case 1: let b = 1; case 1: let b = 2; }"
+            )]
+        ),
+        "Duplicate case label 1 in Main.main."
+    );
+}
+
+// Escape sequences
+
+#[test]
+fn tab_escape_is_not_a_character() {
+    let flags = "--ext literals --ext string-escapes";
+
+    assert!(compile(flags, &[&main_class("do Output.printString(\"a\\tb\");")]).is_ok());
+    assert_eq!(
+        error(flags, &[&main_class("let a = '\\t';")]),
+        "Main.main: \\t stands for four spaces, so it can't be a character literal"
+    );
+}
+
+#[test]
+fn strings_and_character_literals_reject_the_same_unknown_escapes() {
+    let flags = "--ext literals --ext string-escapes";
+
+    assert_eq!(
+        error(flags, &[&main_class("do Output.printString(\"\\q\");")]),
+        "Main.main: unknown escape sequence \\q"
+    );
+    assert_eq!(
+        error(flags, &[&main_class("let a = '\\q';")]),
+        "Main.main: unknown escape sequence \\q"
+    );
+}
+
+// break and continue
+
+#[test]
+fn break_leaves_a_switch_without_an_enclosing_loop() {
+    let source = main_class("switch (a) { case 1: break; default: let b = 2; }");
+
+    assert!(compile("--ext loops --ext switch", &[&source]).is_ok());
+}
+
+#[test]
+fn break_outside_of_a_loop_or_switch() {
+    assert_eq!(
+        error("--ext loops", &[&main_class("if (a) { break; }")]),
+        "'break' outside of a loop or switch in Main.main."
+    );
+}
+
+#[test]
+fn continue_needs_a_loop_even_inside_a_switch() {
+    let source = main_class("switch (a) { case 1: continue; }");
+
+    assert_eq!(
+        error("--ext loops --ext switch", &[&source]),
+        "'continue' outside of a loop in Main.main."
+    );
+    assert!(compile(
+        "--ext loops --ext switch",
+        &[&main_class(
+            "while (a) { switch (a) { case 1: continue; } }"
+        )]
+    )
+    .is_ok());
+}
+
+// Constants
+
+// A class Main declaring `declarations`, with a function `main` holding `statements`.
+fn class_with(declarations: &str, statements: &str) -> String {
+    format!(
+        "class Main {{ {} function void main() {{ var int a; {} return; }} }}",
+        declarations, statements
+    )
+}
+
+#[test]
+fn constants_and_variables_cannot_share_a_name_in_either_order() {
+    let expected = "A is already declared in class Main.";
+
+    assert_eq!(
+        error(
+            "--ext constants",
+            &[&class_with("field int A; const int A = 1;", "")]
+        ),
+        expected
+    );
+    assert_eq!(
+        error(
+            "--ext constants",
+            &[&class_with("const int A = 1; field int A;", "")]
+        ),
+        expected
+    );
+    assert_eq!(
+        error(
+            "--ext constants",
+            &[&class_with("const int A = 1; static int A;", "")]
+        ),
+        expected
+    );
+}
+
+#[test]
+fn constants_cannot_be_assigned() {
+    assert_eq!(
+        error(
+            "--ext constants",
+            &[&class_with("const int A = 1;", "let A = 2;")]
+        ),
+        "Cannot assign to constant A in Main.main."
+    );
+}
+
+#[test]
+fn constants_cannot_be_indexed_or_called() {
+    let declarations = "const int A = 1;";
+
+    assert_eq!(
+        error(
+            "--ext constants",
+            &[&class_with(declarations, "let a = A[1];")]
+        ),
+        "A is a constant in Main.main, so it isn't an array."
+    );
+    assert_eq!(
+        error(
+            "--ext constants",
+            &[&class_with(declarations, "let a = A.foo();")]
+        ),
+        "A is a constant in Main.main, so it has no methods to call."
+    );
+    assert_eq!(
+        error(
+            "--ext constants",
+            &[&class_with(declarations, "do A.foo();")]
+        ),
+        "A is a constant in Main.main, so it has no methods to call."
+    );
+}
+
+#[test]
+fn constant_types() {
+    assert_eq!(
+        error(
+            "--ext constants",
+            &[&class_with("const boolean B = 5;", "")]
+        ),
+        "Boolean constant B in class Main must be true or false, but its value is 5."
+    );
+    assert_eq!(
+        error("--ext constants", &[&class_with("const Foo F = 1;", "")]),
+        "Constants must be of type int, char or boolean, but class Main declares constants of type Foo."
+    );
+    assert!(compile(
+        "--ext constants",
+        &[&class_with(
+            "const boolean T = true, F = ~T; const char C = 65;",
+            ""
+        )]
+    )
+    .is_ok());
+}
+
+// Compound assignment
+
+#[test]
+fn compound_assignment_symbols_must_be_adjacent() {
+    let flags = "--ext compound-assignment";
+
+    assert!(compile(
+        flags,
+        &[&main_class("let a += 1; let a++; let b--; let b -= a;")]
+    )
+    .is_ok());
+    assert_eq!(
+        error(flags, &[&main_class("let a + = 1;")]),
+        "Expected symbol: =. Found: +. "
+    );
+    assert_eq!(
+        error(flags, &[&main_class("let a+ +;")]),
+        "Expected symbol: =. Found: +. "
+    );
+}
+
+#[test]
+fn compound_assignment_needs_its_extension() {
+    assert_eq!(
+        error("", &[&main_class("let a += 1;")]),
+        "Feature compound-assignment ('+=' in Main.main) is not part of standard Jack. Enable it with --ext compound-assignment or --edition extended."
+    );
+    assert_eq!(
+        error("", &[&main_class("let a--;")]),
+        "Feature compound-assignment ('--' in Main.main) is not part of standard Jack. Enable it with --ext compound-assignment or --edition extended."
+    );
+    assert!(compile("", &[&main_class("let a = a--b;")]).is_ok());
+}
+
+// Variables
+
+#[test]
+fn undeclared_variables_are_errors() {
+    assert_eq!(
+        error("", &[&main_class("let c = 1;")]),
+        "c is not declared in Main.main."
+    );
+    assert_eq!(
+        error("", &[&main_class("let a = c[1];")]),
+        "c is not declared in Main.main."
+    );
+}
+
+#[test]
+fn block_variables_go_out_of_scope_at_the_end_of_their_block() {
+    let flags = "--ext block-variables";
+
+    assert!(compile(flags, &[&main_class("if (a) { var int c; let c = 1; }")]).is_ok());
+    assert_eq!(
+        error(flags, &[&main_class("if (a) { var int c; } let c = 2;")]),
+        "c is not declared in Main.main."
+    );
+    assert_eq!(
+        error(flags, &[&main_class("while (a) { var int c; } let b = c;")]),
+        "c is not declared in Main.main."
+    );
+}
+
+// Static initializers
+
+#[test]
+fn static_initializers_that_depend_on_each_other() {
+    let a = "class A { static int x = B.get(); function int get() { return x; } }";
+    let b = "class B { static int y = A.get(); function int get() { return y; } }";
+    let main = "class Main { function void main() { return; } }";
+
+    assert_eq!(
+        error("--ext static-initializers", &[a, b, main]),
+        "The static initializers of A, B depend on each other."
+    );
+
+    // The same, through a class without initializers.
+    let a = "class A { static int x = Util.get(); function int get() { return x; } }";
+    let util = "class Util { function int get() { return B.get(); } }";
+
+    assert_eq!(
+        error("--ext static-initializers", &[a, b, util, main]),
+        "The static initializers of A, B depend on each other."
+    );
+}
+
+#[test]
+fn static_initializers_need_a_function_entry_point() {
+    let flags = "--ext static-initializers";
+    let counter = "class Counter { static int count = 1; }";
+
+    assert_eq!(
+        error(flags, &[counter]),
+        "The static initializers of Counter run at the start of Sys.init or Main.main, but the program has neither function."
+    );
+    assert_eq!(
+        error(
+            flags,
+            &[counter, "class Main { method void main() { return; } }"]
+        ),
+        "Main.main runs the static initializers of Counter, so it must be a function."
+    );
+}
+
+#[test]
+fn only_static_variables_have_initializers() {
+    assert_eq!(
+        error(
+            "--ext static-initializers",
+            &["class Point { field int x = 1; }"]
+        ),
+        "Only static variables can have an initializer, but Point.x is a field."
+    );
+}
+
+// Call chains
+
+#[test]
+fn calls_on_a_primitive_result_are_errors_wherever_the_subroutine_is_declared() {
+    let flags = "--ext call-chains";
+    let expected = "Main.main calls bar on the result of Main.foo, but that returns int, which has no methods.";
+
+    let foo_first = "class Main {
+        function int foo() { return 1; }
+        function void main() { do Main.foo().bar(); return; }
+    }";
+    let foo_last = "class Main {
+        function void main() { do foo().bar(); return; }
+        function int foo() { return 1; }
+    }";
+
+    assert_eq!(error(flags, &[foo_first]), expected);
+    assert_eq!(error(flags, &[foo_last]), expected);
+}
+
+#[test]
+fn this_calls_need_an_object() {
+    assert_eq!(
+        error("--ext call-chains", &[&main_class("do this.draw();")]),
+        "Main.main is a function, so it has no 'this' to call methods on."
+    );
+    assert_eq!(
+        error(
+            "--ext static-initializers --ext call-chains",
+            &[&class_with("static int t = this.get();", "")]
+        ),
+        "The static initializers of Main run without an object, so they have no 'this' to call methods on."
+    );
+}
+
+#[test]
+fn methods_on_primitive_variables_are_only_rejected_with_call_chains() {
+    let source = main_class("do a.draw();");
+
+    assert!(compile("", &[&source]).is_ok());
+    assert_eq!(
+        error("--ext call-chains", &[&source]),
+        "a is of type int in Main.main, so it has no methods to call."
+    );
+}
@@ -0,0 +1,243 @@
+// Golden-file tests. Every directory under tests/ holds Jack classes next to the expected
+// compiler output (Foo.jack -> Foo.xml). The classes of a directory are compiled in memory as one
+// program and each is compared with its golden file.
+//
+// A directory may contain a `jackc.flags` file with command line options (for example
+// `--ext literals`) used to compile every class in it.
+//
+// Classes listed in tests/known-failures, one path per line relative to tests/, are expected not
+// to match their golden file yet. A mismatch is reported but doesn't fail the run, and --bless
+// leaves their golden files alone. A listed class that matches fails the run, so that it gets
+// taken off the list.
+//
+// Usage:
+//   cargo test --test golden                          compare exactly
+//   cargo test --test golden -- --ignore-whitespace   ignore differences in whitespace
+//   cargo test --test golden -- --bless               regenerate the golden files
+//
+// Setting UPDATE_EXPECT=1 is equivalent to --bless, and EXPECT_IGNORE_WHITESPACE=1 to
+// --ignore-whitespace. Any other argument is treated as a filter on the class path.
+
+mod common;
+
+use similar::TextDiff;
+use std::{
+    collections::BTreeMap,
+    env, fs,
+    path::{Path, PathBuf},
+    process,
+};
+use walkdir::WalkDir;
+
+struct HarnessOptions {
+    bless: bool,
+    ignore_whitespace: bool,
+    filters: Vec<String>,
+}
+
+impl HarnessOptions {
+    fn from_env() -> Self {
+        let mut options = HarnessOptions {
+            bless: env::var("UPDATE_EXPECT").is_ok_and(|value| value == "1"),
+            ignore_whitespace: env::var("EXPECT_IGNORE_WHITESPACE").is_ok_and(|value| value == "1"),
+            filters: Vec::new(),
+        };
+
+        for argument in env::args().skip(1) {
+            match argument.as_str() {
+                "--bless" => options.bless = true,
+                "--ignore-whitespace" => options.ignore_whitespace = true,
+                _ if argument.starts_with('-') => {}
+                _ => options.filters.push(argument),
+            }
+        }
+
+        options
+    }
+
+    fn matches(&self, path: &Path) -> bool {
+        let path = path.to_string_lossy();
+        self.filters.is_empty() || self.filters.iter().any(|filter| path.contains(filter))
+    }
+}
+
+fn main() {
+    let options = HarnessOptions::from_env();
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests");
+
+    let mut failures = 0;
+    let mut passed = 0;
+    let mut blessed = 0;
+    let mut known = 0;
+
+    let known_failures = known_failures(&root);
+
+    for (directory, jack_paths) in programs(&root) {
+        if !jack_paths
+            .iter()
+            .any(|jack_path| options.matches(jack_path))
+        {
+            continue;
+        }
+
+        let outputs = match compile(&directory, &jack_paths) {
+            Ok(outputs) => outputs,
+            Err(error) => {
+                let name = directory
+                    .strip_prefix(&root)
+                    .unwrap_or(&directory)
+                    .display();
+                println!("test {} ... FAILED\n  compile error: {}", name, error);
+                failures += 1;
+                continue;
+            }
+        };
+
+        for (jack_path, actual) in jack_paths.iter().zip(outputs) {
+            if !options.matches(jack_path) {
+                continue;
+            }
+
+            let name = jack_path.strip_prefix(&root).unwrap_or(jack_path).display();
+            let golden_path = jack_path.with_extension("xml");
+
+            let known_failure = known_failures.contains(jack_path);
+
+            if options.bless && known_failure {
+                println!("test {} ... known failure, not blessed", name);
+                known += 1;
+                continue;
+            }
+
+            if options.bless {
+                let expected = fs::read_to_string(&golden_path).unwrap_or_default();
+                if expected != actual {
+                    fs::write(&golden_path, &actual).expect("failed to write golden file");
+                    println!("test {} ... blessed", name);
+                    blessed += 1;
+                } else {
+                    println!("test {} ... ok", name);
+                    passed += 1;
+                }
+                continue;
+            }
+
+            let expected = match fs::read_to_string(&golden_path) {
+                Ok(expected) => expected,
+                Err(_) => {
+                    println!(
+                        "test {} ... FAILED\n  missing golden file {} (run with --bless to create it)",
+                        name,
+                        golden_path.display()
+                    );
+                    failures += 1;
+                    continue;
+                }
+            };
+
+            let (expected, actual) = if options.ignore_whitespace {
+                (
+                    normalize_whitespace(&expected),
+                    normalize_whitespace(&actual),
+                )
+            } else {
+                (expected, actual)
+            };
+
+            if expected == actual && known_failure {
+                println!(
+                    "test {} ... FAILED\n  matches its golden file, remove it from tests/known-failures",
+                    name
+                );
+                failures += 1;
+            } else if expected == actual {
+                println!("test {} ... ok", name);
+                passed += 1;
+            } else if known_failure {
+                println!("test {} ... known failure", name);
+                known += 1;
+            } else {
+                let golden_name = golden_path.display().to_string();
+                let diff = TextDiff::from_lines(&expected, &actual);
+                println!("test {} ... FAILED", name);
+                print!(
+                    "{}",
+                    diff.unified_diff()
+                        .context_radius(3)
+                        .header(&golden_name, "actual")
+                );
+                failures += 1;
+            }
+        }
+    }
+
+    println!(
+        "\ngolden result: {} passed; {} failed; {} known failures; {} blessed",
+        passed, failures, known, blessed
+    );
+
+    if failures > 0 {
+        println!("rerun with --bless or UPDATE_EXPECT=1 if the new output is intended");
+        process::exit(1);
+    }
+}
+
+// The classes listed in tests/known-failures. Blank lines and lines starting with # are skipped.
+fn known_failures(root: &Path) -> Vec<PathBuf> {
+    fs::read_to_string(root.join("known-failures"))
+        .unwrap_or_default()
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| root.join(line))
+        .collect()
+}
+
+// The Jack classes under `root`, grouped by directory.
+fn programs(root: &Path) -> BTreeMap<PathBuf, Vec<PathBuf>> {
+    let mut programs: BTreeMap<PathBuf, Vec<PathBuf>> = BTreeMap::new();
+
+    for path in WalkDir::new(root)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.into_path())
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|extension| extension == "jack")
+        })
+    {
+        let directory = path.parent().unwrap_or(root).to_path_buf();
+        programs.entry(directory).or_default().push(path);
+    }
+
+    for paths in programs.values_mut() {
+        paths.sort();
+    }
+
+    programs
+}
+
+fn compile(directory: &Path, jack_paths: &[PathBuf]) -> anyhow::Result<Vec<String>> {
+    let flags = fs::read_to_string(directory.join("jackc.flags")).unwrap_or_default();
+
+    let mut sources = Vec::new();
+
+    for jack_path in jack_paths {
+        sources.push(fs::read_to_string(jack_path)?);
+    }
+
+    common::compile(&flags, &sources)?
+        .outputs
+        .into_iter()
+        .map(|output| Ok(String::from_utf8(output)?))
+        .collect()
+}
+
+// Trims every line, collapses inner runs of whitespace and drops blank lines.
+fn normalize_whitespace(text: &str) -> String {
+    text.lines()
+        .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|line| !line.is_empty())
+        .map(|line| line + "\n")
+        .collect()
+}
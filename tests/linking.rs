@@ -0,0 +1,191 @@
+// Runs the jackc binary on a copy of tests/Linking: multiple roots, library directories given with
+// -L and JACKPATH, and the classes linked from them.
+//
+// tests/Linking/App uses Logger, which Util defines, and Geometry, which both Library and
+// OtherLibrary define. Library's Geometry uses Maths, which only OtherLibrary defines.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+use walkdir::WalkDir;
+
+struct Run {
+    success: bool,
+    stdout: String,
+    stderr: String,
+}
+
+// A fresh copy of tests/Linking, so that the outputs jackc writes don't end up in the source tree.
+fn fixtures(name: &str) -> PathBuf {
+    let source = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/Linking");
+    let copy = Path::new(env!("CARGO_TARGET_TMPDIR")).join(name);
+
+    let _ = fs::remove_dir_all(&copy);
+
+    for entry in WalkDir::new(&source) {
+        let entry = entry.unwrap();
+        let target = copy.join(entry.path().strip_prefix(&source).unwrap());
+
+        if entry.file_type().is_dir() {
+            fs::create_dir_all(&target).unwrap();
+        } else if entry
+            .path()
+            .extension()
+            .is_some_and(|extension| extension == "jack")
+        {
+            fs::copy(entry.path(), &target).unwrap();
+        }
+    }
+
+    copy
+}
+
+fn jackc(directory: &Path, arguments: &[&str], jack_path: Option<&str>) -> Run {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_jackc"));
+    command.current_dir(directory).args(arguments);
+
+    match jack_path {
+        Some(jack_path) => command.env("JACKPATH", jack_path),
+        None => command.env_remove("JACKPATH"),
+    };
+
+    let output = command.output().unwrap();
+
+    Run {
+        success: output.status.success(),
+        stdout: String::from_utf8(output.stdout).unwrap(),
+        stderr: String::from_utf8(output.stderr).unwrap(),
+    }
+}
+
+#[test]
+fn links_from_l_before_jackpath_and_follows_library_dependencies() {
+    let directory = fixtures("library_order");
+    let run = jackc(
+        &directory,
+        &["App", "Util", "-L", "Library"],
+        Some("OtherLibrary"),
+    );
+
+    assert!(run.success, "{}", run.stderr);
+    assert_eq!(
+        run.stdout,
+        "Linked classes of App:
+  Main (App/Main.jack)
+  Logger (Util/Logger.jack)
+  Geometry (Library/Geometry.jack)
+  Maths (OtherLibrary/Maths.jack)
+"
+    );
+
+    // Linked classes are written next to the program, not into the library.
+    for class in ["Main", "Geometry", "Maths"] {
+        assert!(directory
+            .join("App")
+            .join(class)
+            .with_extension("xml")
+            .exists());
+    }
+    assert!(directory.join("Util/Logger.xml").exists());
+    assert!(!directory.join("Library/Geometry.xml").exists());
+}
+
+#[test]
+fn the_first_library_directory_that_defines_a_class_wins() {
+    let directory = fixtures("jackpath_order");
+    let run = jackc(
+        &directory,
+        &["App", "Util"],
+        Some(&format!("OtherLibrary{}Library", separator())),
+    );
+
+    assert!(run.success, "{}", run.stderr);
+    assert_eq!(
+        run.stdout,
+        "Linked classes of App:
+  Main (App/Main.jack)
+  Logger (Util/Logger.jack)
+  Geometry (OtherLibrary/Geometry.jack)
+"
+    );
+}
+
+#[test]
+fn classes_of_the_roots_must_be_unique() {
+    let directory = fixtures("duplicate");
+    let run = jackc(&directory, &["App", "Util", "Duplicate"], None);
+
+    assert!(!run.success);
+    assert_eq!(
+        run.stderr,
+        "Application error: Class Logger is defined in both Util/Logger.jack and Duplicate/Logger.jack.\n"
+    );
+}
+
+#[test]
+fn every_subdirectory_is_a_program_of_its_own() {
+    let directory = fixtures("subdirectories");
+    let run = jackc(&directory, &["."], None);
+
+    // Util and Duplicate both define Logger, and both libraries define Geometry, but each
+    // directory is compiled on its own.
+    assert!(run.success, "{}", run.stderr);
+    assert_eq!(run.stdout, "");
+    assert!(directory.join("Duplicate/Logger.xml").exists());
+    assert!(directory.join("OtherLibrary/Geometry.xml").exists());
+}
+
+#[test]
+fn library_directories_must_exist() {
+    let directory = fixtures("missing_library");
+
+    let run = jackc(&directory, &["App", "-L", "Missing"], None);
+    assert!(!run.success);
+    assert_eq!(
+        run.stderr,
+        "Application error: Library directory Missing given with -L doesn't exist or isn't a directory.\n"
+    );
+
+    let run = jackc(&directory, &["App", "-L", "Util/Logger.jack"], None);
+    assert!(!run.success);
+
+    let run = jackc(&directory, &["App", "Util"], Some("Missing"));
+    assert!(run.success, "{}", run.stderr);
+    assert_eq!(
+        run.stderr,
+        "warning: JACKPATH entry Missing doesn't exist or isn't a directory, so it is skipped.\n"
+    );
+}
+
+#[test]
+fn errors_in_library_classes_name_the_file() {
+    let directory = fixtures("broken_library");
+
+    fs::create_dir(directory.join("Broken")).unwrap();
+    fs::write(
+        directory.join("Broken/Logger.jack"),
+        "class Logger { function void log() { let count = 1; return; } }",
+    )
+    .unwrap();
+
+    let run = jackc(&directory, &["App"], Some("Broken"));
+
+    assert!(!run.success);
+    assert_eq!(
+        run.stderr,
+        format!(
+            "Application error: {}: count is not declared in Logger.log.\n",
+            Path::new("Broken").join("Logger.jack").display()
+        )
+    );
+}
+
+fn separator() -> &'static str {
+    if cfg!(windows) {
+        ";"
+    } else {
+        ":"
+    }
+}